@@ -0,0 +1,88 @@
+#![feature(test)]
+
+extern crate test;
+
+#[path = "../src/spatial.rs"]
+#[allow(dead_code)]
+mod spatial;
+
+use glam::Vec2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use spatial::SpatialHash;
+use test::Bencher;
+
+
+const CELL_SIZE: f32 = 2.0;
+const AREA: f32 = 40.0;
+
+fn circles(count: usize) -> Vec<(Vec2, f32)> {
+	let mut rng = ChaCha8Rng::seed_from_u64(0);
+	(0..count)
+		.map(|_| {
+			(
+				Vec2::new(rng.gen_range(-AREA, AREA), rng.gen_range(-AREA, AREA)),
+				rng.gen_range(0.2, 0.5),
+			)
+		})
+		.collect()
+}
+
+// the all pairs test contactdamage used before the broadphase
+fn brute_force(circles: &[(Vec2, f32)]) -> usize {
+	let mut contacts = 0;
+	for (a, (position, radius)) in circles.iter().enumerate() {
+		for (b, (t_position, t_radius)) in circles.iter().enumerate() {
+			if a != b && (*position - *t_position).length() < radius + t_radius {
+				contacts += 1;
+			}
+		}
+	}
+	contacts
+}
+
+fn spatial_hash(hash: &mut SpatialHash<usize>, circles: &[(Vec2, f32)]) -> usize {
+	hash.clear();
+	for (id, (position, radius)) in circles.iter().enumerate() {
+		hash.insert(id, *position, *radius);
+	}
+	let mut contacts = 0;
+	for (a, (position, radius)) in circles.iter().enumerate() {
+		contacts += hash
+			.query_radius(*position, *radius)
+			.into_iter()
+			.filter(|b| a != *b)
+			.count();
+	}
+	contacts
+}
+
+fn bench_brute_force(bencher: &mut Bencher, count: usize) {
+	let circles = circles(count);
+	bencher.iter(|| brute_force(test::black_box(&circles)));
+}
+
+fn bench_spatial_hash(bencher: &mut Bencher, count: usize) {
+	let circles = circles(count);
+	let mut hash = SpatialHash::new(CELL_SIZE);
+	assert_eq!(spatial_hash(&mut hash, &circles), brute_force(&circles));
+	bencher.iter(|| spatial_hash(&mut hash, test::black_box(&circles)));
+}
+
+#[bench]
+fn brute_force_100(bencher: &mut Bencher) { bench_brute_force(bencher, 100); }
+
+#[bench]
+fn spatial_hash_100(bencher: &mut Bencher) { bench_spatial_hash(bencher, 100); }
+
+#[bench]
+fn brute_force_1000(bencher: &mut Bencher) { bench_brute_force(bencher, 1000); }
+
+#[bench]
+fn spatial_hash_1000(bencher: &mut Bencher) { bench_spatial_hash(bencher, 1000); }
+
+#[bench]
+fn brute_force_5000(bencher: &mut Bencher) { bench_brute_force(bencher, 5000); }
+
+#[bench]
+fn spatial_hash_5000(bencher: &mut Bencher) { bench_spatial_hash(bencher, 5000); }
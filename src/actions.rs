@@ -0,0 +1,204 @@
+use anyhow::Error;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{HashMap, HashSet},
+	path::{Path, PathBuf},
+};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use crate::{
+	input::{GamepadAxis, GamepadButton, Input, MAX_PLAYERS},
+	resources,
+};
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+	MoveX,
+	MoveY,
+	AimX,
+	AimY,
+	Fire,
+	Restart,
+	Pause,
+	Quicksave,
+	Quickload,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Trigger {
+	Key(VirtualKeyCode),
+	Mouse(MouseButton),
+	GamepadButton(GamepadButton),
+	GamepadAxis(GamepadAxis),
+}
+
+impl Trigger {
+	fn value(&self, input: &Input, player: usize) -> f32 {
+		let gamepad = &input.gamepads[player];
+		let pressed = match self {
+			Trigger::Key(key) => player == 0 && input.keys_down.contains(key),
+			Trigger::Mouse(button) => player == 0 && input.buttons_down.contains(button),
+			Trigger::GamepadButton(button) => gamepad.buttons.contains(button),
+			Trigger::GamepadAxis(axis) => return gamepad.axis(*axis),
+		};
+		if pressed {
+			1.0
+		} else {
+			0.0
+		}
+	}
+
+	// whether the trigger was pressed since the last tick, even if it's already released
+	fn tapped(&self, input: &Input, player: usize) -> bool {
+		match self {
+			Trigger::Key(key) => player == 0 && input.keys_pressed.contains(key),
+			Trigger::Mouse(button) => player == 0 && input.buttons_pressed.contains(button),
+			Trigger::GamepadButton(button) => input.gamepads[player].just_pressed(*button),
+			Trigger::GamepadAxis(_) => false,
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+	pub action: Action,
+	pub trigger: Trigger,
+	#[serde(default = "Binding::default_scale")]
+	pub scale: f32,
+}
+
+impl Binding {
+	fn default_scale() -> f32 { 1.0 }
+}
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bindings {
+	pub bindings: Vec<Binding>,
+}
+
+impl Bindings {
+	pub fn path() -> Option<PathBuf> {
+		dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("bindings.ron"))
+	}
+
+	// user bindings replace the defaults entirely, so a broken file falls back to the defaults
+	pub fn load() -> Result<Self, Error> {
+		if let Some(path) = Self::path().filter(|path| path.is_file()) {
+			match Self::read(&path) {
+				Ok(bindings) => {
+					info!("loaded bindings from {}", path.display());
+					return Ok(bindings);
+				},
+				Err(error) => warn!("couldn't load bindings from {}: {}", path.display(), error),
+			}
+		}
+		Ok(ron::de::from_bytes(&resources::get_file("bindings.ron")?)?)
+	}
+
+	fn read(path: &Path) -> Result<Self, Error> {
+		Ok(ron::de::from_str(&std::fs::read_to_string(path)?)?)
+	}
+}
+
+
+#[derive(Clone, Copy, Default)]
+struct ActionState {
+	value: f32,
+	down: bool,
+	pressed: bool,
+	released: bool,
+	held: u64,
+}
+
+// action states advance once per fixed tick, so edges line up with the simulation
+pub struct Actions {
+	players: Vec<HashMap<Action, ActionState>>,
+	delta: f32,
+}
+
+impl Actions {
+	pub fn new(delta: f32) -> Self {
+		Self {
+			players: vec![HashMap::new(); MAX_PLAYERS],
+			delta,
+		}
+	}
+
+	// bindings of the same action add up, so opposing directions cancel out
+	pub fn update(&mut self, bindings: &Bindings, input: &Input) {
+		for (player, states) in self.players.iter_mut().enumerate() {
+			let mut values = HashMap::new();
+			let mut tapped = HashSet::new();
+			for binding in &bindings.bindings {
+				*values.entry(binding.action).or_insert(0.0) +=
+					binding.trigger.value(input, player) * binding.scale;
+				if binding.trigger.tapped(input, player) {
+					tapped.insert(binding.action);
+				}
+			}
+			for state in states.values_mut() {
+				state.value = 0.0;
+			}
+			for (action, value) in values {
+				states.entry(action).or_default().value = value.clamp(-1.0, 1.0);
+			}
+			for (action, state) in states.iter_mut() {
+				let tapped = tapped.contains(action);
+				let down = state.value > 0.5 || tapped;
+				state.pressed = tapped || (down && !state.down);
+				state.released = !down && state.down;
+				state.held = if down { state.held + 1 } else { 0 };
+				state.down = down;
+			}
+		}
+	}
+
+	fn state(&self, player: usize, action: Action) -> ActionState {
+		self.players
+			.get(player)
+			.and_then(|states| states.get(&action))
+			.copied()
+			.unwrap_or_default()
+	}
+
+	pub fn value(&self, player: usize, action: Action) -> f32 { self.state(player, action).value }
+
+	pub fn pressed(&self, player: usize, action: Action) -> bool { self.state(player, action).down }
+
+	pub fn just_pressed(&self, player: usize, action: Action) -> bool {
+		self.state(player, action).pressed
+	}
+
+	pub fn just_released(&self, player: usize, action: Action) -> bool {
+		self.state(player, action).released
+	}
+
+	// seconds the action has been held, including the current tick
+	pub fn held(&self, player: usize, action: Action) -> f32 {
+		self.state(player, action).held as f32 * self.delta
+	}
+
+	pub fn any_pressed(&self, action: Action) -> bool {
+		(0..self.players.len()).any(|player| self.pressed(player, action))
+	}
+
+	pub fn any_just_pressed(&self, action: Action) -> bool {
+		(0..self.players.len()).any(|player| self.just_pressed(player, action))
+	}
+
+	// held actions stay down so they don't count as pressed again on the next update
+	pub fn clear_edges(&mut self) {
+		for state in self
+			.players
+			.iter_mut()
+			.flat_map(|states| states.values_mut())
+		{
+			state.pressed = false;
+			state.released = false;
+		}
+	}
+}
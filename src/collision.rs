@@ -0,0 +1,552 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use shipyard::EntityId;
+use std::collections::HashSet;
+
+use crate::components::Transform;
+
+
+const CIRCLE_SEGMENTS: usize = 16;
+const MAX_SWEEP_STEPS: usize = 32;
+const MIN_SWEEP_STEP: f32 = 0.01;
+
+
+// shapes are in local units, scaled by the transform and moved by the collider offset
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+	Circle { radius: f32 },
+	// ignores the rotation of the transform
+	Aabb { half_extents: Vec2 },
+	// rotates with the transform
+	Obb { half_extents: Vec2 },
+	// convex, the points can be in either winding order
+	Polygon { points: Vec<Vec2> },
+}
+
+impl Default for Shape {
+	fn default() -> Self { Shape::Circle { radius: 1.0 } }
+}
+
+impl Shape {
+	pub fn to_world(&self, offset: Vec2, transform: &Transform) -> WorldShape {
+		let position = Vec2::new(transform.position.x(), transform.position.y());
+		let scale = Vec2::new(transform.scale[0], transform.scale[1]);
+		let angle = transform.rotation.x();
+		let point = |local: Vec2| position + rotate((offset + local) * scale, angle);
+		match self {
+			Shape::Circle { radius } => WorldShape::Circle {
+				center: point(Vec2::zero()),
+				radius: radius * scale.x().abs().max(scale.y().abs()),
+			},
+			Shape::Aabb { half_extents } => {
+				let center = position + offset * scale;
+				let extents = *half_extents * scale;
+				let extents = Vec2::new(extents.x().abs(), extents.y().abs());
+				WorldShape::Aabb {
+					min: center - extents,
+					max: center + extents,
+				}
+			},
+			Shape::Obb { half_extents } => {
+				let (x, y) = (half_extents.x(), half_extents.y());
+				WorldShape::Polygon {
+					points: vec![
+						point(Vec2::new(-x, -y)),
+						point(Vec2::new(x, -y)),
+						point(Vec2::new(x, y)),
+						point(Vec2::new(-x, y)),
+					],
+				}
+			},
+			Shape::Polygon { points } => WorldShape::Polygon {
+				points: points.iter().map(|local| point(*local)).collect(),
+			},
+		}
+	}
+}
+
+
+// a shape placed in the world, recomputed from the collider and transform every tick
+#[derive(Clone, Debug)]
+pub enum WorldShape {
+	Circle { center: Vec2, radius: f32 },
+	Aabb { min: Vec2, max: Vec2 },
+	Polygon { points: Vec<Vec2> },
+}
+
+impl WorldShape {
+	// bounding circle for the broadphase
+	pub fn bounds(&self) -> (Vec2, f32) {
+		match self {
+			WorldShape::Circle { center, radius } => (*center, *radius),
+			WorldShape::Aabb { min, max } => ((*min + *max) * 0.5, (*max - *min).length() * 0.5),
+			WorldShape::Polygon { points } => {
+				let center = centroid(points);
+				let radius = points
+					.iter()
+					.map(|point| (*point - center).length())
+					.fold(0.0, f32::max);
+				(center, radius)
+			},
+		}
+	}
+
+	pub fn translated(&self, offset: Vec2) -> WorldShape {
+		match self {
+			WorldShape::Circle { center, radius } => WorldShape::Circle {
+				center: *center + offset,
+				radius: *radius,
+			},
+			WorldShape::Aabb { min, max } => WorldShape::Aabb {
+				min: *min + offset,
+				max: *max + offset,
+			},
+			WorldShape::Polygon { points } => WorldShape::Polygon {
+				points: points.iter().map(|point| *point + offset).collect(),
+			},
+		}
+	}
+
+	// closed outline for debug drawing
+	pub fn outline(&self) -> Vec<Vec2> {
+		match self {
+			WorldShape::Circle { center, radius } => (0..CIRCLE_SEGMENTS)
+				.map(|i| {
+					let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+					*center + Vec2::new(angle.cos(), angle.sin()) * *radius
+				})
+				.collect(),
+			WorldShape::Aabb { min, max } => corners(*min, *max).to_vec(),
+			WorldShape::Polygon { points } => points.clone(),
+		}
+	}
+}
+
+
+// overlapping pairs of the current and the previous tick, kept in detection order so events are deterministic
+pub struct Contacts {
+	pairs: Vec<(EntityId, EntityId, Contact)>,
+	touching: HashSet<(EntityId, EntityId)>,
+	previous: Vec<(EntityId, EntityId)>,
+	previous_touching: HashSet<(EntityId, EntityId)>,
+}
+
+impl Contacts {
+	pub fn new() -> Self {
+		Self {
+			pairs: Vec::new(),
+			touching: HashSet::new(),
+			previous: Vec::new(),
+			previous_touching: HashSet::new(),
+		}
+	}
+
+	// replaces the pairs with the ones found this tick
+	pub fn update(&mut self, pairs: Vec<(EntityId, EntityId, Contact)>) {
+		self.previous = self.pairs.iter().map(|(a, b, _)| (*a, *b)).collect();
+		self.previous_touching = std::mem::take(&mut self.touching);
+		self.touching = pairs.iter().map(|(a, b, _)| (*a, *b)).collect();
+		self.pairs = pairs;
+	}
+
+	pub fn iter(&self) -> std::slice::Iter<(EntityId, EntityId, Contact)> { self.pairs.iter() }
+
+	pub fn was_touching(&self, a: EntityId, b: EntityId) -> bool {
+		self.previous_touching.contains(&(a, b))
+	}
+
+	// pairs that touched last tick but don't anymore
+	pub fn ended(&self) -> impl Iterator<Item = (EntityId, EntityId)> + '_ {
+		self.previous
+			.iter()
+			.copied()
+			.filter(move |pair| !self.touching.contains(pair))
+	}
+
+	pub fn clear(&mut self) {
+		self.pairs.clear();
+		self.touching.clear();
+		self.previous.clear();
+		self.previous_touching.clear();
+	}
+}
+
+
+// separating normal pointing from the first shape to the second and the penetration depth
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+	pub normal: Vec2,
+	pub depth: f32,
+}
+
+impl Contact {
+	fn flipped(self) -> Self {
+		Contact {
+			normal: -self.normal,
+			depth: self.depth,
+		}
+	}
+}
+
+pub fn intersect(a: &WorldShape, b: &WorldShape) -> Option<Contact> {
+	use WorldShape::*;
+	match (a, b) {
+		(
+			Circle {
+				center: ca,
+				radius: ra,
+			},
+			Circle {
+				center: cb,
+				radius: rb,
+			},
+		) => circle_circle(*ca, *ra, *cb, *rb),
+		(Circle { center, radius }, Aabb { min, max }) => circle_aabb(*center, *radius, *min, *max),
+		(Aabb { min, max }, Circle { center, radius }) => {
+			circle_aabb(*center, *radius, *min, *max).map(Contact::flipped)
+		},
+		(Circle { center, radius }, Polygon { points }) => circle_polygon(*center, *radius, points),
+		(Polygon { points }, Circle { center, radius }) => {
+			circle_polygon(*center, *radius, points).map(Contact::flipped)
+		},
+		(
+			Aabb {
+				min: mina,
+				max: maxa,
+			},
+			Aabb {
+				min: minb,
+				max: maxb,
+			},
+		) => aabb_aabb(*mina, *maxa, *minb, *maxb),
+		(Aabb { min, max }, Polygon { points }) => polygon_polygon(&corners(*min, *max), points),
+		(Polygon { points }, Aabb { min, max }) => polygon_polygon(points, &corners(*min, *max)),
+		(Polygon { points: a }, Polygon { points: b }) => polygon_polygon(a, b),
+	}
+}
+
+// tests the shapes at evenly spaced points along their movement so fast shapes can't skip past thin ones
+pub fn intersect_swept(
+	a: &WorldShape, sweep_a: Vec2, b: &WorldShape, sweep_b: Vec2,
+) -> Option<Contact> {
+	let step = a.bounds().1.min(b.bounds().1).max(MIN_SWEEP_STEP);
+	let steps = (((sweep_a - sweep_b).length() / step).ceil() as usize).min(MAX_SWEEP_STEPS);
+	if steps == 0 {
+		return intersect(a, b);
+	}
+	(0..=steps).find_map(|i| {
+		let t = i as f32 / steps as f32;
+		intersect(&a.translated(sweep_a * t), &b.translated(sweep_b * t))
+	})
+}
+
+fn circle_circle(ca: Vec2, ra: f32, cb: Vec2, rb: f32) -> Option<Contact> {
+	let delta = cb - ca;
+	let distance = delta.length();
+	if distance >= ra + rb {
+		return None;
+	}
+	Some(Contact {
+		normal: direction(delta),
+		depth: ra + rb - distance,
+	})
+}
+
+fn circle_aabb(center: Vec2, radius: f32, min: Vec2, max: Vec2) -> Option<Contact> {
+	let closest = center.max(min).min(max);
+	if closest != center {
+		let delta = closest - center;
+		let distance = delta.length();
+		if distance >= radius {
+			return None;
+		}
+		return Some(Contact {
+			normal: delta / distance,
+			depth: radius - distance,
+		});
+	}
+	// the center is inside the box, push out through the nearest side
+	let sides = [
+		(center.x() - min.x(), Vec2::new(-1.0, 0.0)),
+		(max.x() - center.x(), Vec2::new(1.0, 0.0)),
+		(center.y() - min.y(), Vec2::new(0.0, -1.0)),
+		(max.y() - center.y(), Vec2::new(0.0, 1.0)),
+	];
+	let (distance, normal) =
+		sides.iter().copied().fold(
+			sides[0],
+			|best, side| if side.0 < best.0 { side } else { best },
+		);
+	Some(Contact {
+		normal: -normal,
+		depth: distance + radius,
+	})
+}
+
+fn aabb_aabb(mina: Vec2, maxa: Vec2, minb: Vec2, maxb: Vec2) -> Option<Contact> {
+	let x = maxa.x().min(maxb.x()) - mina.x().max(minb.x());
+	let y = maxa.y().min(maxb.y()) - mina.y().max(minb.y());
+	if x <= 0.0 || y <= 0.0 {
+		return None;
+	}
+	let delta = (minb + maxb) - (mina + maxa);
+	Some(if x < y {
+		Contact {
+			normal: Vec2::new(delta.x().signum(), 0.0),
+			depth: x,
+		}
+	} else {
+		Contact {
+			normal: Vec2::new(0.0, delta.y().signum()),
+			depth: y,
+		}
+	})
+}
+
+fn circle_polygon(center: Vec2, radius: f32, points: &[Vec2]) -> Option<Contact> {
+	if points.is_empty() {
+		return None;
+	}
+	// the edge normals plus the axis toward the closest vertex cover every separating direction
+	let closest = points.iter().copied().fold(points[0], |best, point| {
+		if (point - center).length_squared() < (best - center).length_squared() {
+			point
+		} else {
+			best
+		}
+	});
+	let mut axes = edge_normals(points);
+	if closest != center {
+		axes.push((closest - center).normalize());
+	}
+	let mut best: Option<Contact> = None;
+	for axis in axes {
+		let (min, max) = project(points, axis);
+		let middle = center.dot(axis);
+		let depth = overlap((middle - radius, middle + radius), (min, max));
+		if depth <= 0.0 {
+			return None;
+		}
+		if best.map_or(true, |best| depth < best.depth) {
+			best = Some(Contact {
+				normal: axis,
+				depth,
+			});
+		}
+	}
+	best.map(|contact| orient(contact, center, centroid(points)))
+}
+
+fn polygon_polygon(a: &[Vec2], b: &[Vec2]) -> Option<Contact> {
+	let mut best: Option<Contact> = None;
+	for axis in edge_normals(a).into_iter().chain(edge_normals(b)) {
+		let depth = overlap(project(a, axis), project(b, axis));
+		if depth <= 0.0 {
+			return None;
+		}
+		if best.map_or(true, |best| depth < best.depth) {
+			best = Some(Contact {
+				normal: axis,
+				depth,
+			});
+		}
+	}
+	best.map(|contact| orient(contact, centroid(a), centroid(b)))
+}
+
+
+fn rotate(point: Vec2, angle: f32) -> Vec2 {
+	let (sin, cos) = angle.sin_cos();
+	Vec2::new(
+		point.x() * cos - point.y() * sin,
+		point.x() * sin + point.y() * cos,
+	)
+}
+
+fn corners(min: Vec2, max: Vec2) -> [Vec2; 4] {
+	[
+		min,
+		Vec2::new(max.x(), min.y()),
+		max,
+		Vec2::new(min.x(), max.y()),
+	]
+}
+
+fn centroid(points: &[Vec2]) -> Vec2 {
+	points.iter().fold(Vec2::zero(), |sum, point| sum + *point) / points.len().max(1) as f32
+}
+
+fn edge_normals(points: &[Vec2]) -> Vec<Vec2> {
+	(0..points.len())
+		.map(|i| points[(i + 1) % points.len()] - points[i])
+		.filter(|edge| edge.length_squared() > 0.0)
+		.map(|edge| Vec2::new(-edge.y(), edge.x()).normalize())
+		.collect()
+}
+
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+	points
+		.iter()
+		.map(|point| point.dot(axis))
+		.fold((f32::MAX, f32::MIN), |(min, max), d| {
+			(min.min(d), max.max(d))
+		})
+}
+
+fn overlap(a: (f32, f32), b: (f32, f32)) -> f32 { a.1.min(b.1) - a.0.max(b.0) }
+
+// flips the normal so it points from a to b
+fn orient(contact: Contact, a: Vec2, b: Vec2) -> Contact {
+	if (b - a).dot(contact.normal) < 0.0 {
+		contact.flipped()
+	} else {
+		contact
+	}
+}
+
+// shapes sharing a center are pushed apart along an arbitrary but fixed axis
+fn direction(delta: Vec2) -> Vec2 {
+	if delta.length_squared() > 0.0 {
+		delta.normalize()
+	} else {
+		Vec2::new(0.0, 1.0)
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn circle(x: f32, y: f32, radius: f32) -> WorldShape {
+		WorldShape::Circle {
+			center: Vec2::new(x, y),
+			radius,
+		}
+	}
+
+	fn aabb(x: f32, y: f32, half_x: f32, half_y: f32) -> WorldShape {
+		WorldShape::Aabb {
+			min: Vec2::new(x - half_x, y - half_y),
+			max: Vec2::new(x + half_x, y + half_y),
+		}
+	}
+
+	fn square(x: f32, y: f32, half: f32, clockwise: bool) -> WorldShape {
+		let mut points =
+			corners(Vec2::new(x - half, y - half), Vec2::new(x + half, y + half)).to_vec();
+		if clockwise {
+			points.reverse();
+		}
+		WorldShape::Polygon { points }
+	}
+
+	fn assert_contact(contact: Option<Contact>, normal: (f32, f32), depth: f32) {
+		let contact = contact.expect("expected a contact");
+		assert!(
+			(contact.normal - Vec2::new(normal.0, normal.1)).length() < 1e-5,
+			"normal {:?}",
+			contact.normal
+		);
+		assert!(
+			(contact.depth - depth).abs() < 1e-5,
+			"depth {}",
+			contact.depth
+		);
+	}
+
+	#[test]
+	fn circles_hit_and_miss() {
+		assert_contact(
+			intersect(&circle(0.0, 0.0, 1.0), &circle(1.5, 0.0, 1.0)),
+			(1.0, 0.0),
+			0.5,
+		);
+		assert_contact(
+			intersect(&circle(1.5, 0.0, 1.0), &circle(0.0, 0.0, 1.0)),
+			(-1.0, 0.0),
+			0.5,
+		);
+		assert!(intersect(&circle(0.0, 0.0, 1.0), &circle(2.5, 0.0, 1.0)).is_none());
+	}
+
+	#[test]
+	fn circle_and_aabb_hit_and_miss() {
+		let (a, b) = (circle(0.0, 0.0, 1.0), aabb(1.5, 0.0, 1.0, 1.0));
+		assert_contact(intersect(&a, &b), (1.0, 0.0), 0.5);
+		assert_contact(intersect(&b, &a), (-1.0, 0.0), 0.5);
+		assert!(intersect(&a, &aabb(3.0, 0.0, 1.0, 1.0)).is_none());
+	}
+
+	#[test]
+	fn circle_inside_aabb_is_pushed_through_the_nearest_side() {
+		let (a, b) = (circle(0.8, 0.0, 0.5), aabb(0.0, 0.0, 1.0, 1.0));
+		assert_contact(intersect(&a, &b), (-1.0, 0.0), 0.7);
+		assert_contact(intersect(&b, &a), (1.0, 0.0), 0.7);
+	}
+
+	#[test]
+	fn circle_and_polygon_hit_and_miss_in_both_windings() {
+		for &clockwise in &[false, true] {
+			let (a, b) = (circle(0.0, 0.0, 1.0), square(1.5, 0.0, 1.0, clockwise));
+			assert_contact(intersect(&a, &b), (1.0, 0.0), 0.5);
+			assert_contact(intersect(&b, &a), (-1.0, 0.0), 0.5);
+			assert!(intersect(&a, &square(3.0, 0.0, 1.0, clockwise)).is_none());
+		}
+	}
+
+	#[test]
+	fn aabbs_hit_and_miss() {
+		let (a, b) = (aabb(0.0, 0.0, 1.0, 1.0), aabb(0.0, 1.5, 1.0, 1.0));
+		assert_contact(intersect(&a, &b), (0.0, 1.0), 0.5);
+		assert_contact(intersect(&b, &a), (0.0, -1.0), 0.5);
+		assert!(intersect(&a, &aabb(0.0, 3.0, 1.0, 1.0)).is_none());
+	}
+
+	#[test]
+	fn aabb_and_polygon_hit_and_miss() {
+		let (a, b) = (aabb(0.0, 0.0, 1.0, 1.0), square(-1.5, 0.0, 1.0, true));
+		assert_contact(intersect(&a, &b), (-1.0, 0.0), 0.5);
+		assert_contact(intersect(&b, &a), (1.0, 0.0), 0.5);
+		assert!(intersect(&a, &square(-3.0, 0.0, 1.0, true)).is_none());
+	}
+
+	#[test]
+	fn polygons_hit_and_miss_in_both_windings() {
+		for &(clockwise_a, clockwise_b) in
+			&[(false, false), (false, true), (true, false), (true, true)]
+		{
+			let (a, b) = (
+				square(0.0, 0.0, 1.0, clockwise_a),
+				square(1.5, 0.0, 1.0, clockwise_b),
+			);
+			assert_contact(intersect(&a, &b), (1.0, 0.0), 0.5);
+			assert_contact(intersect(&b, &a), (-1.0, 0.0), 0.5);
+			assert!(intersect(&a, &square(3.0, 0.0, 1.0, clockwise_b)).is_none());
+		}
+	}
+
+	#[test]
+	fn orient_points_the_normal_from_a_to_b() {
+		let contact = Contact {
+			normal: Vec2::new(-1.0, 0.0),
+			depth: 0.5,
+		};
+		let oriented = orient(contact, Vec2::zero(), Vec2::new(2.0, 0.0));
+		assert_eq!(oriented.normal, Vec2::new(1.0, 0.0));
+		let kept = orient(contact, Vec2::new(2.0, 0.0), Vec2::zero());
+		assert_eq!(kept.normal, Vec2::new(-1.0, 0.0));
+	}
+
+	#[test]
+	fn fast_circle_sweeps_through_a_thin_box() {
+		let (bullet, wall) = (circle(-4.0, 0.0, 0.1), aabb(0.0, 0.0, 0.05, 1.0));
+		let sweep = Vec2::new(8.0, 0.0);
+		assert!(intersect(&bullet, &wall).is_none());
+		assert!(intersect(&bullet.translated(sweep), &wall).is_none());
+		assert!(intersect_swept(&bullet, Vec2::zero(), &wall, Vec2::zero()).is_none());
+		assert!(intersect_swept(&bullet, sweep, &wall, Vec2::zero()).is_some());
+		// relative movement counts, so a wall moving into a still bullet hits as well
+		assert!(intersect_swept(&wall, -sweep, &bullet, Vec2::zero()).is_some());
+	}
+}
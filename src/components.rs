@@ -1,38 +1,253 @@
+use serde::{Deserialize, Serialize};
+
+use crate::collision::Shape;
+
+
+// times are in game time, see Clock::time
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Cooldown {
+	pub duration: f32,
+	#[serde(default)]
+	pub ready_at: f32,
+}
+
+impl Cooldown {
+	pub fn new(duration: f32) -> Self {
+		Self {
+			duration,
+			ready_at: 0.0,
+		}
+	}
+
+	pub fn is_ready(&self, now: f32) -> bool { now >= self.ready_at }
+
+	pub fn remaining(&self, now: f32) -> f32 { (self.ready_at - now).max(0.0) }
+
+	pub fn trigger(&mut self, now: f32) { self.trigger_for(now, self.duration); }
+
+	pub fn trigger_for(&mut self, now: f32, duration: f32) { self.ready_at = now + duration; }
+
+	// triggers the cooldown if it is ready, returns whether it was
+	pub fn try_trigger(&mut self, now: f32) -> bool {
+		if !self.is_ready(now) {
+			return false;
+		}
+		self.trigger(now);
+		true
+	}
+
+	pub fn reset(&mut self) { self.ready_at = 0.0; }
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Transform {
 	pub position: glam::Vec3,
 	pub scale: [f32; 2],
 	pub rotation: glam::Vec3,
 }
+pub struct PreviousTransform {
+	pub position: glam::Vec3,
+	pub rotation: glam::Vec3,
+}
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Sprite {
 	pub color: [f32; 4],
 	pub sprite: [f32; 2],
 }
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Enemy {}
-pub struct Player {}
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Player {
+	#[serde(default)]
+	pub index: usize,
+}
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Life {
 	pub health: f32,
 }
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Physics {
+	#[serde(default)]
+	pub velocity: glam::Vec3,
+	// accumulated from forces during a tick and cleared after integration
+	#[serde(default)]
 	pub acceleration: glam::Vec3,
-	pub deceleration: f32,
+	#[serde(default = "Physics::default_mass")]
+	pub mass: f32,
+	// fraction of the velocity lost per second
+	#[serde(default)]
+	pub drag: f32,
+	#[serde(default)]
+	pub max_speed: Option<f32>,
+	#[serde(default)]
+	pub angular_velocity: f32,
+	// continuous collision detection for fast bodies, swept in systems::collision and by rapier
+	#[serde(default)]
+	pub ccd: bool,
 }
+
+impl Physics {
+	fn default_mass() -> f32 { 1.0 }
+
+	// bodies without a positive mass are immovable
+	pub fn inverse_mass(&self) -> f32 {
+		if self.mass > 0.0 {
+			1.0 / self.mass
+		} else {
+			0.0
+		}
+	}
+
+	pub fn apply_force(&mut self, force: glam::Vec3) {
+		self.acceleration += force * self.inverse_mass();
+	}
+
+	pub fn apply_impulse(&mut self, impulse: glam::Vec3) {
+		self.velocity += impulse * self.inverse_mass();
+	}
+}
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Weapon {
-	pub repeat: f32,
-	pub last: f32,
+	pub cooldown: Cooldown,
+	// keeps firing while held instead of once per press
+	#[serde(default)]
+	pub automatic: bool,
 }
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SelfDamage {
 	pub damage: f32,
 }
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ContactDamage {
 	pub damage: f32,
 	pub once: bool,
+	// damages entities of the same faction
+	#[serde(default)]
+	pub friendly_fire: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Layer {
+	Player,
+	PlayerProjectile,
+	Enemy,
+	EnemyProjectile,
+	Pickup,
+	Wall,
+}
+
+impl Layer {
+	pub const ALL: [Layer; 6] = [
+		Layer::Player,
+		Layer::PlayerProjectile,
+		Layer::Enemy,
+		Layer::EnemyProjectile,
+		Layer::Pickup,
+		Layer::Wall,
+	];
+}
+
+bitflags::bitflags! {
+	// written as a list of layers in data files
+	#[derive(Default, Serialize, Deserialize)]
+	#[serde(from = "Vec<Layer>", into = "Vec<Layer>")]
+	pub struct Layers: u32 {
+		const PLAYER = 1 << 0;
+		const PLAYER_PROJECTILE = 1 << 1;
+		const ENEMY = 1 << 2;
+		const ENEMY_PROJECTILE = 1 << 3;
+		const PICKUP = 1 << 4;
+		const WALL = 1 << 5;
+	}
+}
+
+impl From<Layer> for Layers {
+	fn from(layer: Layer) -> Self {
+		match layer {
+			Layer::Player => Layers::PLAYER,
+			Layer::PlayerProjectile => Layers::PLAYER_PROJECTILE,
+			Layer::Enemy => Layers::ENEMY,
+			Layer::EnemyProjectile => Layers::ENEMY_PROJECTILE,
+			Layer::Pickup => Layers::PICKUP,
+			Layer::Wall => Layers::WALL,
+		}
+	}
+}
+
+impl From<Vec<Layer>> for Layers {
+	fn from(layers: Vec<Layer>) -> Self {
+		layers
+			.into_iter()
+			.fold(Layers::empty(), |layers, layer| layers | layer.into())
+	}
+}
+
+impl From<Layers> for Vec<Layer> {
+	fn from(layers: Layers) -> Self {
+		Layer::ALL
+			.iter()
+			.copied()
+			.filter(|layer| layers.contains((*layer).into()))
+			.collect()
+	}
+}
+
+// an entity collides with the entities on the layers in its mask
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Collider {
+	#[serde(default)]
+	pub shape: Shape,
+	#[serde(default)]
+	pub offset: glam::Vec2,
+	pub layer: Layers,
+	#[serde(default)]
+	pub mask: Layers,
+	// solid colliders are pushed apart and bounce off each other
+	#[serde(default)]
+	pub solid: bool,
+	#[serde(default)]
+	pub restitution: f32,
+	// impulse applied to the other entity when a solid contact starts
+	#[serde(default)]
+	pub knockback: f32,
+}
+
+impl Collider {
+	pub fn interacts(&self, other: &Collider) -> bool { self.mask.intersects(other.layer) }
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Faction {
+	Player,
+	Enemy,
+	Neutral,
+}
+
+// the entity that spawned this one, e.g. the shooter of a projectile
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Owner {
+	pub entity: shipyard::EntityId,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Spawner {
-	pub spawnrate: f32,
-	pub last: f32,
-	pub player: shipyard::EntityId,
+	// the duration is the average delay between spawns
+	pub cooldown: Cooldown,
+	// spawns around the origin without a player
+	pub player: Option<shipyard::EntityId>,
+	pub prefabs: Vec<String>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Paused {}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Aim {
+	pub direction: Option<glam::Vec3>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Camera {
 	pub eye: glam::Vec3,
 	pub target: glam::Vec3,
@@ -43,6 +258,47 @@ pub struct Camera {
 	pub zfar: f32,
 }
 
+impl Camera {
+	pub fn view_projection(&self, aspect: f32) -> glam::Mat4 {
+		let view = glam::Mat4::look_at_lh(self.eye, self.target, self.up);
+		let proj =
+			glam::Mat4::perspective_lh(self.fovy.to_radians(), aspect, self.znear, self.zfar);
+		proj * view
+	}
+
+	// turns a cursor position in pixels into the world position on the plane at depth z
+	pub fn unproject(
+		&self, cursor: glam::Vec2, viewport: glam::Vec2, z: f32,
+	) -> Option<glam::Vec3> {
+		if viewport.x() <= 0.0 || viewport.y() <= 0.0 {
+			return None;
+		}
+		let inverse = self.view_projection(viewport.x() / viewport.y()).inverse();
+		let x = cursor.x() / viewport.x() * 2.0 - 1.0;
+		let y = 1.0 - cursor.y() / viewport.y() * 2.0;
+		let point = |depth: f32| {
+			let point = inverse * glam::Vec4::new(x, y, depth, 1.0);
+			point.truncate() / point.w()
+		};
+		let (near, far) = (point(0.0), point(1.0));
+		if (far.z() - near.z()).abs() < std::f32::EPSILON {
+			return None;
+		}
+		let t = (z - near.z()) / (far.z() - near.z());
+		Some(near + (far - near) * t)
+	}
+
+	// the pose the camera settles at when following an entity at this position
+	pub fn following(&self, position: glam::Vec3) -> Self {
+		Self {
+			eye: glam::Vec3::new(position.x(), position.y(), 0.0),
+			target: glam::Vec3::new(position.x(), position.y(), 100.0),
+			..self.clone()
+		}
+	}
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CameraFollow {
 	pub entity: shipyard::EntityId,
 }
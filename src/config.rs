@@ -0,0 +1,54 @@
+use anyhow::Error;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+use std::path::{Path, PathBuf};
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PresentMode {
+	Vsync,
+	Mailbox,
+	Immediate,
+}
+
+#[derive(Clone, Debug, SmartDefault, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	pub seed: Option<u64>,
+	#[default(true)]
+	pub pause_on_focus_lost: bool,
+	#[default(PresentMode::Mailbox)]
+	pub present_mode: PresentMode,
+	// frames per second, uncapped when none
+	pub frame_cap: Option<f32>,
+	#[default(Some(30.0))]
+	pub unfocused_frame_cap: Option<f32>,
+}
+
+impl Config {
+	pub fn path() -> Option<PathBuf> {
+		dirs::config_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("config.ron"))
+	}
+
+	pub fn load() -> Self {
+		let path = match Self::path() {
+			Some(path) if path.is_file() => path,
+			_ => return Self::default(),
+		};
+		match Self::read(&path) {
+			Ok(config) => {
+				info!("loaded config from {}", path.display());
+				config
+			},
+			Err(error) => {
+				warn!("couldn't load config from {}: {}", path.display(), error);
+				Self::default()
+			},
+		}
+	}
+
+	fn read(path: &Path) -> Result<Self, Error> {
+		Ok(ron::de::from_str(&std::fs::read_to_string(path)?)?)
+	}
+}
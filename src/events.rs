@@ -0,0 +1,89 @@
+use shipyard::EntityId;
+
+
+// events are kept for the whole frame, fixed systems only see the ones of the current tick and
+// have to run after the systems that send them
+pub struct Events<T> {
+	events: Vec<T>,
+	// index of the first event sent in the current tick
+	tick_start: usize,
+}
+
+impl<T> Events<T> {
+	pub fn new() -> Self {
+		Self {
+			events: Vec::new(),
+			tick_start: 0,
+		}
+	}
+
+	pub fn send(&mut self, event: T) { self.events.push(event); }
+
+	// events sent in the current tick
+	pub fn iter(&self) -> std::slice::Iter<T> { self.events[self.tick_start..].iter() }
+
+	pub fn is_empty(&self) -> bool { self.events.len() == self.tick_start }
+
+	// events sent in all ticks of the current frame, for update and render systems
+	pub fn iter_frame(&self) -> std::slice::Iter<T> { self.events.iter() }
+
+	pub fn begin_tick(&mut self) { self.tick_start = self.events.len(); }
+
+	pub fn clear(&mut self) {
+		self.events.clear();
+		self.tick_start = 0;
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct DamageDealt {
+	pub source: EntityId,
+	pub target: EntityId,
+	pub amount: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct EntityDied {
+	pub entity: EntityId,
+	pub position: Option<glam::Vec3>,
+	pub enemy: bool,
+	pub player: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProjectileFired {
+	pub entity: EntityId,
+	pub owner: EntityId,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerHit {
+	pub player: EntityId,
+	pub source: EntityId,
+	pub amount: f32,
+}
+
+// pairs are reported once per tick, the normal points from a to b
+#[derive(Clone, Debug)]
+pub struct CollisionEnter {
+	pub a: EntityId,
+	pub b: EntityId,
+	pub normal: glam::Vec2,
+	pub depth: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct CollisionStay {
+	pub a: EntityId,
+	pub b: EntityId,
+	pub normal: glam::Vec2,
+	pub depth: f32,
+}
+
+// also sent when one of the entities was deleted
+#[derive(Clone, Debug)]
+pub struct CollisionExit {
+	pub a: EntityId,
+	pub b: EntityId,
+}
@@ -0,0 +1,362 @@
+use anyhow::{anyhow, Error};
+use log::{info, warn};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::input::{Gamepad, GamepadAxis, GamepadButton, Input, MAX_PLAYERS};
+
+
+const DEADZONE: f32 = 0.2;
+
+
+pub type DeviceId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+	Connected(DeviceId),
+	Disconnected(DeviceId),
+	Button(DeviceId, GamepadButton, bool),
+	Axis(DeviceId, GamepadAxis, f32),
+}
+
+pub trait GamepadSource {
+	fn poll(&mut self) -> Vec<GamepadEvent>;
+}
+
+
+pub struct Gamepads {
+	source: Option<Box<dyn GamepadSource>>,
+	devices: HashMap<DeviceId, Gamepad>,
+	// the device assigned to each player, devices are assigned to the first free player on connect
+	players: Vec<Option<DeviceId>>,
+	deadzone: f32,
+}
+
+impl Gamepads {
+	pub fn new() -> Self {
+		Self {
+			source: None,
+			devices: HashMap::new(),
+			players: vec![None; MAX_PLAYERS],
+			deadzone: DEADZONE,
+		}
+	}
+
+	pub fn set_source(&mut self, source: Box<dyn GamepadSource>) {
+		self.disconnect_all();
+		self.source = Some(source);
+	}
+
+	pub fn set_deadzone(&mut self, deadzone: f32) { self.deadzone = deadzone.clamp(0.0, 0.99); }
+
+	pub fn player(&self, device: DeviceId) -> Option<usize> {
+		self.players.iter().position(|slot| *slot == Some(device))
+	}
+
+	pub fn poll(&mut self) {
+		let events = match self.source.as_mut() {
+			Some(source) => source.poll(),
+			None => return,
+		};
+		for event in events {
+			match event {
+				GamepadEvent::Connected(device) => self.connect(device),
+				GamepadEvent::Disconnected(device) => self.disconnect(device),
+				GamepadEvent::Button(device, button, pressed) => {
+					let gamepad = self.device(device);
+					if pressed {
+						if gamepad.buttons.insert(button) {
+							gamepad.pressed.insert(button);
+						}
+					} else {
+						gamepad.buttons.remove(&button);
+					}
+				},
+				GamepadEvent::Axis(device, axis, value) => {
+					self.device(device).axes.insert(axis, value);
+				},
+			}
+		}
+	}
+
+	// writes the state of the assigned devices with dead zones applied into the input
+	pub fn apply(&mut self, input: &mut Input) {
+		for (player, slot) in self.players.iter().enumerate() {
+			let mut gamepad = slot
+				.and_then(|device| self.devices.get(&device))
+				.map(|gamepad| self.filter(gamepad))
+				.unwrap_or_default();
+			// presses not yet seen by a tick are kept until the tick ends
+			gamepad
+				.pressed
+				.extend(input.gamepads[player].pressed.iter().copied());
+			input.gamepads[player] = gamepad;
+		}
+		for gamepad in self.devices.values_mut() {
+			gamepad.pressed.clear();
+		}
+	}
+
+	fn device(&mut self, device: DeviceId) -> &mut Gamepad {
+		if !self.devices.contains_key(&device) {
+			self.connect(device);
+		}
+		self.devices.get_mut(&device).unwrap()
+	}
+
+	fn connect(&mut self, device: DeviceId) {
+		if self.devices.contains_key(&device) {
+			return;
+		}
+		self.devices.insert(device, Gamepad::default());
+		match self.players.iter().position(Option::is_none) {
+			Some(player) => {
+				self.players[player] = Some(device);
+				info!("gamepad {} connected as player {}", device, player + 1);
+			},
+			None => warn!("gamepad {} connected but all players are assigned", device),
+		}
+	}
+
+	// a disconnected player keeps its slot free until the next device connects
+	fn disconnect(&mut self, device: DeviceId) {
+		self.devices.remove(&device);
+		if let Some(player) = self.player(device) {
+			self.players[player] = None;
+			info!("gamepad {} of player {} disconnected", device, player + 1);
+		}
+	}
+
+	fn disconnect_all(&mut self) {
+		self.devices.clear();
+		for slot in &mut self.players {
+			*slot = None;
+		}
+	}
+
+	fn filter(&self, gamepad: &Gamepad) -> Gamepad {
+		let mut filtered = gamepad.clone();
+		for &(x, y) in &[
+			(GamepadAxis::LeftStickX, GamepadAxis::LeftStickY),
+			(GamepadAxis::RightStickX, GamepadAxis::RightStickY),
+		] {
+			let (dx, dy) = deadzone(gamepad.axis(x), gamepad.axis(y), self.deadzone);
+			filtered.axes.insert(x, dx);
+			filtered.axes.insert(y, dy);
+		}
+		filtered
+	}
+}
+
+// radial dead zone rescaled so the output starts at zero at the edge of the dead zone
+fn deadzone(x: f32, y: f32, deadzone: f32) -> (f32, f32) {
+	let magnitude = (x * x + y * y).sqrt();
+	if magnitude <= deadzone {
+		return (0.0, 0.0);
+	}
+	let scale = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0) / magnitude;
+	(x * scale, y * scale)
+}
+
+
+pub struct GilrsSource {
+	gilrs: gilrs::Gilrs,
+	pending: Vec<GamepadEvent>,
+}
+
+impl GilrsSource {
+	pub fn new() -> Result<Self, Error> {
+		let gilrs = gilrs::Gilrs::new().map_err(|error| anyhow!("{}", error))?;
+		// devices that are already plugged in don't send a connected event
+		let pending = gilrs
+			.gamepads()
+			.map(|(id, _)| GamepadEvent::Connected(id.into()))
+			.collect();
+		Ok(Self { gilrs, pending })
+	}
+}
+
+impl GamepadSource for GilrsSource {
+	fn poll(&mut self) -> Vec<GamepadEvent> {
+		use gilrs::EventType;
+		let mut events = std::mem::take(&mut self.pending);
+		while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+			let device: DeviceId = id.into();
+			match event {
+				EventType::Connected => events.push(GamepadEvent::Connected(device)),
+				EventType::Disconnected => events.push(GamepadEvent::Disconnected(device)),
+				EventType::ButtonPressed(button, _) => {
+					if let Some(button) = map_button(button) {
+						events.push(GamepadEvent::Button(device, button, true));
+					}
+				},
+				EventType::ButtonReleased(button, _) => {
+					if let Some(button) = map_button(button) {
+						events.push(GamepadEvent::Button(device, button, false));
+					}
+				},
+				EventType::AxisChanged(axis, value, _) => {
+					if let Some(axis) = map_axis(axis) {
+						events.push(GamepadEvent::Axis(device, axis, value));
+					}
+				},
+				_ => (),
+			}
+		}
+		events
+	}
+}
+
+fn map_button(button: gilrs::Button) -> Option<GamepadButton> {
+	use gilrs::Button::*;
+	Some(match button {
+		South => GamepadButton::South,
+		East => GamepadButton::East,
+		North => GamepadButton::North,
+		West => GamepadButton::West,
+		LeftTrigger => GamepadButton::LeftBumper,
+		RightTrigger => GamepadButton::RightBumper,
+		LeftTrigger2 => GamepadButton::LeftTrigger,
+		RightTrigger2 => GamepadButton::RightTrigger,
+		Select => GamepadButton::Select,
+		Start => GamepadButton::Start,
+		LeftThumb => GamepadButton::LeftStick,
+		RightThumb => GamepadButton::RightStick,
+		DPadUp => GamepadButton::DPadUp,
+		DPadDown => GamepadButton::DPadDown,
+		DPadLeft => GamepadButton::DPadLeft,
+		DPadRight => GamepadButton::DPadRight,
+		_ => return None,
+	})
+}
+
+fn map_axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+	use gilrs::Axis::*;
+	Some(match axis {
+		LeftStickX => GamepadAxis::LeftStickX,
+		LeftStickY => GamepadAxis::LeftStickY,
+		RightStickX => GamepadAxis::RightStickX,
+		RightStickY => GamepadAxis::RightStickY,
+		_ => return None,
+	})
+}
+
+
+// a scriptable source for driving gamepads without hardware, clones share the same queue
+#[derive(Clone, Default)]
+pub struct VirtualSource {
+	events: Rc<RefCell<Vec<GamepadEvent>>>,
+}
+
+impl VirtualSource {
+	pub fn new() -> Self { Self::default() }
+
+	pub fn send(&self, event: GamepadEvent) { self.events.borrow_mut().push(event); }
+
+	pub fn connect(&self, device: DeviceId) { self.send(GamepadEvent::Connected(device)); }
+
+	pub fn disconnect(&self, device: DeviceId) { self.send(GamepadEvent::Disconnected(device)); }
+
+	pub fn press(&self, device: DeviceId, button: GamepadButton) {
+		self.send(GamepadEvent::Button(device, button, true));
+	}
+
+	pub fn release(&self, device: DeviceId, button: GamepadButton) {
+		self.send(GamepadEvent::Button(device, button, false));
+	}
+
+	pub fn axis(&self, device: DeviceId, axis: GamepadAxis, value: f32) {
+		self.send(GamepadEvent::Axis(device, axis, value));
+	}
+}
+
+impl GamepadSource for VirtualSource {
+	fn poll(&mut self) -> Vec<GamepadEvent> { self.events.borrow_mut().drain(..).collect() }
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn gamepads() -> (Gamepads, VirtualSource) {
+		let source = VirtualSource::new();
+		let mut gamepads = Gamepads::new();
+		gamepads.set_source(Box::new(source.clone()));
+		(gamepads, source)
+	}
+
+	#[test]
+	fn deadzone_is_radial_and_rescaled() {
+		assert_eq!(deadzone(0.1, 0.1, 0.2), (0.0, 0.0));
+		assert_eq!(deadzone(0.0, -0.19, 0.2), (0.0, 0.0));
+		let (x, y) = deadzone(0.0, 0.6, 0.2);
+		assert_eq!(x, 0.0);
+		assert!((y - 0.5).abs() < 1e-6);
+		let (x, y) = deadzone(1.0, 1.0, 0.2);
+		assert!(((x * x + y * y).sqrt() - 1.0).abs() < 1e-6);
+	}
+
+	#[test]
+	fn axes_are_filtered_into_input() {
+		let (mut gamepads, source) = gamepads();
+		source.connect(3);
+		source.axis(3, GamepadAxis::LeftStickX, 0.1);
+		source.axis(3, GamepadAxis::RightStickY, -0.6);
+		gamepads.poll();
+		let mut input = Input::new();
+		gamepads.apply(&mut input);
+		assert_eq!(input.gamepads[0].axis(GamepadAxis::LeftStickX), 0.0);
+		assert!((input.gamepads[0].axis(GamepadAxis::RightStickY) + 0.5).abs() < 1e-6);
+	}
+
+	#[test]
+	fn devices_are_assigned_to_free_player_slots() {
+		let (mut gamepads, source) = gamepads();
+		source.connect(7);
+		source.connect(2);
+		gamepads.poll();
+		assert_eq!(gamepads.player(7), Some(0));
+		assert_eq!(gamepads.player(2), Some(1));
+
+		source.disconnect(7);
+		gamepads.poll();
+		assert_eq!(gamepads.player(7), None);
+		assert_eq!(gamepads.player(2), Some(1));
+
+		source.connect(9);
+		gamepads.poll();
+		assert_eq!(gamepads.player(9), Some(0));
+	}
+
+	#[test]
+	fn hotplugging_clears_the_player_input() {
+		let (mut gamepads, source) = gamepads();
+		source.connect(1);
+		source.press(1, GamepadButton::South);
+		gamepads.poll();
+		let mut input = Input::new();
+		gamepads.apply(&mut input);
+		assert!(input.gamepads[0].buttons.contains(&GamepadButton::South));
+		assert!(input.gamepads[0].just_pressed(GamepadButton::South));
+
+		input.end_tick();
+		source.disconnect(1);
+		gamepads.poll();
+		gamepads.apply(&mut input);
+		assert!(input.gamepads[0].buttons.is_empty());
+	}
+
+	#[test]
+	fn extra_devices_stay_unassigned() {
+		let (mut gamepads, source) = gamepads();
+		for device in 0..=MAX_PLAYERS {
+			source.connect(device);
+		}
+		gamepads.poll();
+		assert_eq!(gamepads.player(MAX_PLAYERS), None);
+		source.disconnect(0);
+		source.connect(MAX_PLAYERS + 1);
+		gamepads.poll();
+		assert_eq!(gamepads.player(MAX_PLAYERS + 1), Some(0));
+	}
+}
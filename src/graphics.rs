@@ -57,6 +57,7 @@ pub struct Renderer {
 	pub camera_args: wgpu::Buffer,
 
 	pub camera: Option<shipyard::EntityId>,
+	pub debug_colliders: bool,
 
 	pub width: u32,
 	pub height: u32,
@@ -466,6 +467,7 @@ impl Renderer {
 			bg_pipeline,
 			camera_args,
 			camera: None,
+			debug_colliders: false,
 			width: 1,
 			height: 1,
 		})
@@ -1,13 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+
+pub const MAX_PLAYERS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+	South,
+	East,
+	North,
+	West,
+	LeftBumper,
+	RightBumper,
+	LeftTrigger,
+	RightTrigger,
+	Select,
+	Start,
+	LeftStick,
+	RightStick,
+	DPadUp,
+	DPadDown,
+	DPadLeft,
+	DPadRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+	LeftStickX,
+	LeftStickY,
+	RightStickX,
+	RightStickY,
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Gamepad {
+	pub buttons: HashSet<GamepadButton>,
+	// buttons pressed since the last tick, including ones already released again
+	pub pressed: HashSet<GamepadButton>,
+	pub axes: HashMap<GamepadAxis, f32>,
+}
+
+impl Gamepad {
+	pub fn axis(&self, axis: GamepadAxis) -> f32 { self.axes.get(&axis).copied().unwrap_or(0.0) }
+
+	pub fn just_pressed(&self, button: GamepadButton) -> bool { self.pressed.contains(&button) }
+
+	pub fn clear(&mut self) {
+		self.buttons.clear();
+		self.pressed.clear();
+		self.axes.clear();
+	}
+}
+
+
+// keyboard and mouse always belong to the first player, gamepads are indexed by player
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Input {
-	pub keys_down: std::collections::HashSet<winit::event::VirtualKeyCode>,
+	pub keys_down: HashSet<VirtualKeyCode>,
+	pub buttons_down: HashSet<MouseButton>,
+	// edges since the last tick, latched so presses shorter than a tick aren't lost
+	pub keys_pressed: HashSet<VirtualKeyCode>,
+	pub keys_released: HashSet<VirtualKeyCode>,
+	pub buttons_pressed: HashSet<MouseButton>,
+	pub buttons_released: HashSet<MouseButton>,
+	// cursor position in pixels from the top left, none while outside the window
+	pub cursor: Option<glam::Vec2>,
+	pub viewport: glam::Vec2,
+	pub gamepads: Vec<Gamepad>,
+	// set when the window loses focus, latched like the other edges
+	pub pause_requested: bool,
 }
 
 impl Input {
 	pub fn new() -> Self {
 		Self {
-			keys_down: std::collections::HashSet::new(),
+			keys_down: HashSet::new(),
+			buttons_down: HashSet::new(),
+			keys_pressed: HashSet::new(),
+			keys_released: HashSet::new(),
+			buttons_pressed: HashSet::new(),
+			buttons_released: HashSet::new(),
+			cursor: None,
+			viewport: glam::Vec2::zero(),
+			gamepads: vec![Gamepad::default(); MAX_PLAYERS],
+			pause_requested: false,
+		}
+	}
+
+	pub fn press_key(&mut self, key: VirtualKeyCode) {
+		// repeated presses from holding the key down are not new presses
+		if self.keys_down.insert(key) {
+			self.keys_pressed.insert(key);
 		}
 	}
 
-	pub fn clear(&mut self) { self.keys_down.clear(); }
+	pub fn release_key(&mut self, key: VirtualKeyCode) {
+		if self.keys_down.remove(&key) {
+			self.keys_released.insert(key);
+		}
+	}
+
+	pub fn press_button(&mut self, button: MouseButton) {
+		if self.buttons_down.insert(button) {
+			self.buttons_pressed.insert(button);
+		}
+	}
+
+	pub fn release_button(&mut self, button: MouseButton) {
+		if self.buttons_down.remove(&button) {
+			self.buttons_released.insert(button);
+		}
+	}
+
+	pub fn just_pressed(&self, key: VirtualKeyCode) -> bool { self.keys_pressed.contains(&key) }
+
+	pub fn just_released(&self, key: VirtualKeyCode) -> bool { self.keys_released.contains(&key) }
+
+	// forgets the edges once a tick has seen them
+	pub fn end_tick(&mut self) {
+		self.keys_pressed.clear();
+		self.keys_released.clear();
+		self.buttons_pressed.clear();
+		self.buttons_released.clear();
+		for gamepad in &mut self.gamepads {
+			gamepad.pressed.clear();
+		}
+		self.pause_requested = false;
+	}
+
+	// only releases held keys and buttons, the cursor and viewport stay valid
+	pub fn clear(&mut self) {
+		self.keys_down.clear();
+		self.buttons_down.clear();
+		self.end_tick();
+		for gamepad in &mut self.gamepads {
+			gamepad.clear();
+		}
+	}
 }
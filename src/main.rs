@@ -114,16 +114,32 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 
+mod actions;
+mod collision;
 mod components;
+mod config;
+mod events;
+mod gamepad;
 mod graphics;
 mod input;
+mod options;
+mod prefab;
+#[cfg(feature = "physics-rapier")]
+mod rapier;
+mod replay;
 mod resources;
+mod rng;
+mod save;
+mod scene;
+mod scheduler;
 mod session;
+mod spatial;
 mod states;
 mod systems;
 mod time;
 mod universe;
 mod util;
+mod workloads;
 
 
 use flamer::flame;
@@ -134,10 +150,20 @@ use winit::{
 	window::Window,
 };
 
-use crate::util::*;
+use crate::{
+	config::Config,
+	options::Options,
+	time::{FrameAccumTimer, FrameLimiter},
+	util::*,
+};
+
+
+const HEADLESS_FRAME_TIME: f32 = 1000.0 / 60.0;
+const HEADLESS_FRAMES: u32 = 600;
+const PACING_INTERVAL: f32 = 1000.0;
 
 fn main() {
-	std::panic::set_hook(create_panic_hook(None));
+	std::panic::set_hook(create_panic_hook(None, None));
 
 	pretty_env_logger::formatted_timed_builder()
 		.filter_level(
@@ -151,14 +177,83 @@ fn main() {
 
 	info!("{}", create_version_string());
 
+	let options = match Options::from_args() {
+		Ok(options) => options,
+		Err(error) => {
+			error!("{}", error);
+			std::process::exit(1);
+		},
+	};
+
+	let config = Config::load();
+	let seed = options.seed.or(config.seed).unwrap_or_else(rand::random);
+	info!("using seed {}", seed);
+
+	if options.headless {
+		headless(options, seed);
+		return;
+	}
+
 	let eventloop = EventLoop::new();
 	let window = create_window(&env!("CARGO_PKG_NAME"), &eventloop);
 
-	async_std::task::block_on(start(eventloop, window));
+	async_std::task::block_on(start(eventloop, window, options, config, seed));
+}
+
+fn setup(universe: &mut universe::Universe, options: &Options) -> CrashInfo {
+	if let Some(path) = &options.replay {
+		if let Err(error) = universe.start_replay(path) {
+			error!("couldn't replay {}: {}", path.display(), error);
+			std::process::exit(1);
+		}
+	}
+	if let Some(path) = &options.record {
+		if let Err(error) = universe.start_recording(path) {
+			error!("couldn't record to {}: {}", path.display(), error);
+			std::process::exit(1);
+		}
+	}
+	universe.push_state::<states::SpaceShooterState>();
+	CrashInfo {
+		seed: universe.seed(),
+		recording: options.record.clone(),
+	}
+}
+
+fn headless(options: Options, seed: u64) {
+	let frames = options.frames.unwrap_or(if options.replay.is_some() {
+		u32::MAX
+	} else {
+		HEADLESS_FRAMES
+	});
+	info!("running {} frames headless", frames);
+
+	let mut universe = match universe::Universe::headless(seed) {
+		Ok(universe) => universe,
+		Err(error) => {
+			error!("couldn't set up the world: {}", error);
+			std::process::exit(1);
+		},
+	};
+	std::panic::set_hook(create_panic_hook(
+		None,
+		Some(setup(&mut universe, &options)),
+	));
+	for _ in 0..frames {
+		universe.step(HEADLESS_FRAME_TIME);
+		if universe.is_replay_finished() {
+			break;
+		}
+	}
+	universe.stop_recording();
+
+	println!("{}", universe.get_status());
 }
 
 #[flame]
-async fn start(eventloop: EventLoop<()>, window: Window) {
+async fn start(
+	eventloop: EventLoop<()>, window: Window, options: Options, config: Config, seed: u64,
+) {
 	let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
 	let surface: wgpu::Surface = unsafe { instance.create_surface(&window) };
 	let adapter: wgpu::Adapter = instance
@@ -179,33 +274,60 @@ async fn start(eventloop: EventLoop<()>, window: Window) {
 		)
 		.await
 		.unwrap();
-	std::panic::set_hook(create_panic_hook(Some(adapter.get_info())));
+	let adapter_info = adapter.get_info();
+	std::panic::set_hook(create_panic_hook(Some(adapter_info.clone()), None));
 	window.set_visible(true);
 
 
 	info!("setting up world");
 
-	let mut universe = universe::Universe::new(device, queue).unwrap();
+	let mut universe = match universe::Universe::new(device, queue, seed) {
+		Ok(universe) => universe,
+		Err(error) => {
+			error!("couldn't set up the world: {}", error);
+			std::process::exit(1);
+		},
+	};
+	universe.set_present_mode(get_present_mode(config.present_mode));
+	universe.set_debug_colliders(options.debug_colliders);
 	universe.create_swapchain(&window, &surface);
-	universe.push_state::<states::SpaceShooterState>();
+	let crash_info = setup(&mut universe, &options);
+	std::panic::set_hook(create_panic_hook(Some(adapter_info), Some(crash_info)));
 
 
 	info!("entering event loop");
 
 	let mut window_has_focus = true;
-	let mut window_mouseover = false;
+	let mut limiter = FrameLimiter::new(config.frame_cap);
+	let mut pacing = FrameAccumTimer::new(60, PACING_INTERVAL);
 
 	eventloop.run(move |event, _, control_flow| {
-		*control_flow = ControlFlow::Poll;
+		let now = std::time::Instant::now();
 		match event {
 			Event::MainEventsCleared => {
 				if *control_flow == ControlFlow::Exit {
 					return;
 				}
-				flame::clear();
-				universe.update();
-				universe.render();
-				window.set_title(universe.get_status().as_str());
+				if limiter.is_ready(now) {
+					flame::clear();
+					universe.update();
+					universe.render();
+					limiter.finish(now);
+					pacing.update();
+					pacing.trigger(|pacing| {
+						window.set_title(universe.get_status().as_str());
+						debug!(
+							"{:.1} fps ({:.3} ms average, {:.3} ms last)",
+							pacing.frames_per_second_smooth(),
+							pacing.frame_time_smooth(),
+							pacing.frame_time()
+						);
+					});
+				}
+				*control_flow = match limiter.next() {
+					Some(next) => ControlFlow::WaitUntil(next),
+					None => ControlFlow::Poll,
+				};
 			},
 			Event::RedrawRequested(_) => {},
 			Event::WindowEvent {
@@ -213,41 +335,40 @@ async fn start(eventloop: EventLoop<()>, window: Window) {
 				window_id,
 			} if window_id == window.id() => {
 				*control_flow = ControlFlow::Exit;
+				universe.stop_recording();
 				flame::dump_json(&mut std::fs::File::create("flame.json").unwrap()).unwrap();
 			},
-			Event::WindowEvent {
-				event: WindowEvent::Resized(_),
-				window_id,
-			} if window_id == window.id() => {
-				info!("resized");
-				universe.create_swapchain(&window, &surface);
-			},
-			Event::WindowEvent {
-				event: WindowEvent::ScaleFactorChanged { .. },
-				window_id,
-			} if window_id == window.id() => {
-				info!("scale changed");
-				universe.create_swapchain(&window, &surface);
-			},
-			Event::WindowEvent {
-				event: WindowEvent::Focused(focused),
-				window_id,
-			} if window_id == window.id() => {
-				window_has_focus = focused;
-			},
-			Event::WindowEvent {
-				event: WindowEvent::CursorEntered { .. },
-				window_id,
-			} if window_id == window.id() => {
-				window_mouseover = true;
-			},
-			Event::WindowEvent {
-				event: WindowEvent::CursorLeft { .. },
-				window_id,
-			} if window_id == window.id() => {
-				window_mouseover = false;
-			},
 			event => {
+				if let Event::WindowEvent {
+					event: ref window_event,
+					window_id,
+				} = event
+				{
+					if window_id == window.id() {
+						match window_event {
+							WindowEvent::Resized(_) => {
+								info!("resized");
+								universe.create_swapchain(&window, &surface);
+							},
+							WindowEvent::ScaleFactorChanged { .. } => {
+								info!("scale changed");
+								universe.create_swapchain(&window, &surface);
+							},
+							WindowEvent::Focused(focused) => {
+								window_has_focus = *focused;
+								limiter.set_cap(if window_has_focus {
+									config.frame_cap
+								} else {
+									config.unfocused_frame_cap.or(config.frame_cap)
+								});
+								if !focused && config.pause_on_focus_lost {
+									universe.request_pause();
+								}
+							},
+							_ => (),
+						}
+					}
+				}
 				universe.event(event);
 			},
 		}
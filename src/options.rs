@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Error};
+use std::path::PathBuf;
+
+
+pub struct Options {
+	pub headless: bool,
+	pub frames: Option<u32>,
+	pub seed: Option<u64>,
+	pub record: Option<PathBuf>,
+	pub replay: Option<PathBuf>,
+	pub debug_colliders: bool,
+}
+
+impl Options {
+	pub fn from_args() -> Result<Self, Error> {
+		let mut options = Self {
+			headless: false,
+			frames: None,
+			seed: None,
+			record: None,
+			replay: None,
+			debug_colliders: false,
+		};
+		let mut args = std::env::args().skip(1).peekable();
+		while let Some(arg) = args.next() {
+			match arg.as_str() {
+				"--headless" => {
+					options.headless = true;
+					if let Some(Ok(frames)) = args.peek().map(|frames| frames.parse::<u32>()) {
+						args.next();
+						options.frames = Some(frames);
+					}
+				},
+				"--seed" => {
+					let seed = args
+						.next()
+						.ok_or_else(|| anyhow!("missing value for --seed"))?;
+					options.seed = Some(
+						seed.parse()
+							.map_err(|_| anyhow!("invalid value for --seed: {}", seed))?,
+					);
+				},
+				"--record" => {
+					options.record = Some(
+						args.next()
+							.ok_or_else(|| anyhow!("missing value for --record"))?
+							.into(),
+					);
+				},
+				"--replay" => {
+					options.replay = Some(
+						args.next()
+							.ok_or_else(|| anyhow!("missing value for --replay"))?
+							.into(),
+					);
+				},
+				"--debug-colliders" => options.debug_colliders = true,
+				_ => return Err(anyhow!("unknown argument: {}", arg)),
+			}
+		}
+		Ok(options)
+	}
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use shipyard::{AllStoragesViewMut, EntitiesViewMut, EntityId, UniqueView};
+use std::{collections::HashMap, mem::discriminant};
+
+use crate::{components::Transform, resources, scene::SceneComponent};
+
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Prefabs {
+	prefabs: HashMap<String, Vec<SceneComponent>>,
+}
+
+impl Prefabs {
+	pub fn load() -> Result<Self, Error> {
+		Ok(ron::de::from_bytes(&resources::get_file("prefabs.ron")?)?)
+	}
+
+	// resolves the components of a prefab with the overrides applied on top
+	pub fn build(
+		&self, name: Option<&str>, overrides: &[SceneComponent],
+	) -> Result<Vec<SceneComponent>, Error> {
+		let mut components = match name {
+			Some(name) => self
+				.prefabs
+				.get(name)
+				.cloned()
+				.ok_or_else(|| anyhow!("unknown prefab: {}", name))?,
+			None => Vec::new(),
+		};
+		for component in overrides {
+			merge(&mut components, component.clone());
+		}
+		Ok(components)
+	}
+}
+
+fn merge(components: &mut Vec<SceneComponent>, component: SceneComponent) {
+	match component {
+		SceneComponent::Position(position) => transform(components).position = position,
+		SceneComponent::Rotation(rotation) => transform(components).rotation = rotation,
+		component => {
+			match components
+				.iter_mut()
+				.find(|existing| discriminant(*existing) == discriminant(&component))
+			{
+				Some(existing) => *existing = component,
+				None => components.push(component),
+			}
+		},
+	}
+}
+
+fn transform(components: &mut Vec<SceneComponent>) -> &mut Transform {
+	let index = match components
+		.iter()
+		.position(|component| matches!(component, SceneComponent::Transform(_)))
+	{
+		Some(index) => index,
+		None => {
+			components.push(SceneComponent::Transform(Transform {
+				position: glam::Vec3::zero(),
+				scale: [1.0, 1.0],
+				rotation: glam::Vec3::zero(),
+			}));
+			components.len() - 1
+		},
+	};
+	match &mut components[index] {
+		SceneComponent::Transform(transform) => transform,
+		_ => unreachable!(),
+	}
+}
+
+pub fn spawn_prefab(
+	all_storages: &AllStoragesViewMut, name: &str, overrides: &[SceneComponent],
+) -> Result<EntityId, Error> {
+	let components =
+		all_storages.run(|prefabs: UniqueView<Prefabs>| prefabs.build(Some(name), overrides))?;
+	let id = all_storages.run(|mut entities: EntitiesViewMut| entities.add_entity((), ()));
+	for component in &components {
+		component.insert(all_storages, id, &HashMap::new())?;
+	}
+	Ok(id)
+}
@@ -0,0 +1,289 @@
+use flamer::flame;
+use rapier2d::{
+	dynamics::{
+		CCDSolver, IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodyHandle, RigidBodySet,
+	},
+	geometry::{
+		BroadPhase, ColliderBuilder, ColliderHandle, ColliderSet, InteractionGroups, NarrowPhase,
+	},
+	na::{Isometry2, Point2, Vector2},
+	pipeline::PhysicsPipeline,
+};
+use shipyard::{EntityId, Get, IntoIter, Shiperator, UniqueView, UniqueViewMut, View, ViewMut};
+use std::collections::HashMap;
+
+use crate::{
+	collision::Shape,
+	components::{Aim, Collider, Physics, Transform},
+	systems::face,
+	time::Clock,
+};
+
+
+// positions closer than this to the last synced one count as unchanged
+const EPSILON: f32 = 1e-4;
+
+
+// the body of an entity and the component state it was last built and synced from
+struct Body {
+	handle: RigidBodyHandle,
+	dynamic: bool,
+	collider: Option<(ColliderHandle, Collider, [f32; 2])>,
+	synced: (glam::Vec2, f32),
+}
+
+// rigid-body backend replacing systems::physics, the components stay the source of truth between ticks
+pub struct RapierWorld {
+	pipeline: PhysicsPipeline,
+	parameters: IntegrationParameters,
+	broad_phase: BroadPhase,
+	narrow_phase: NarrowPhase,
+	ccd_solver: CCDSolver,
+	pub bodies: RigidBodySet,
+	pub colliders: ColliderSet,
+	pub joints: JointSet,
+	entities: HashMap<EntityId, Body>,
+}
+
+impl RapierWorld {
+	pub fn new() -> Self {
+		Self {
+			pipeline: PhysicsPipeline::new(),
+			parameters: IntegrationParameters::default(),
+			broad_phase: BroadPhase::new(),
+			narrow_phase: NarrowPhase::new(),
+			ccd_solver: CCDSolver::new(),
+			bodies: RigidBodySet::new(),
+			colliders: ColliderSet::new(),
+			joints: JointSet::new(),
+			entities: HashMap::new(),
+		}
+	}
+
+	pub fn handle(&self, id: EntityId) -> Option<RigidBodyHandle> {
+		self.entities.get(&id).map(|body| body.handle)
+	}
+
+	pub fn clear(&mut self) { *self = Self::new(); }
+
+	// entities without physics get a static body so solid colliders still block
+	fn insert(&mut self, id: EntityId, transform: &Transform, physics: Option<&Physics>) {
+		let builder = match physics {
+			Some(physics) => RigidBodyBuilder::new_dynamic()
+				.linvel(physics.velocity.x(), physics.velocity.y())
+				.angvel(physics.angular_velocity)
+				.linear_damping(physics.drag)
+				.additional_mass(physics.mass.max(0.0))
+				.ccd_enabled(physics.ccd),
+			None => RigidBodyBuilder::new_static(),
+		};
+		let body = builder
+			.translation(transform.position.x(), transform.position.y())
+			.rotation(transform.rotation.x())
+			.build();
+		let handle = self.bodies.insert(body);
+		self.entities.insert(id, Body {
+			handle,
+			dynamic: physics.is_some(),
+			collider: None,
+			synced: synced(transform),
+		});
+	}
+
+	fn remove(&mut self, id: EntityId) {
+		if let Some(body) = self.entities.remove(&id) {
+			self.bodies
+				.remove(body.handle, &mut self.colliders, &mut self.joints);
+		}
+	}
+
+	// rebuilds the collider when the component or the scale changed since it was built
+	fn update_collider(
+		&mut self, id: EntityId, transform: &Transform, collider: Option<&Collider>,
+	) {
+		let body = self.entities.get_mut(&id).unwrap();
+		let unchanged = match (&body.collider, collider) {
+			(Some((_, built, scale)), Some(collider)) => {
+				built == collider && *scale == transform.scale
+			},
+			(None, None) => true,
+			_ => false,
+		};
+		if unchanged {
+			return;
+		}
+		if let Some((handle, ..)) = body.collider.take() {
+			self.colliders.remove(handle, &mut self.bodies, true);
+		}
+		if let Some(collider) = collider {
+			let builder = shape(&collider.shape, transform)
+				.translation(
+					collider.offset.x() * transform.scale[0],
+					collider.offset.y() * transform.scale[1],
+				)
+				.density(0.0)
+				.restitution(collider.restitution)
+				.collision_groups(InteractionGroups::new(
+					collider.layer.bits(),
+					collider.mask.bits(),
+				))
+				// only solid colliders take part in the contact response
+				.sensor(!collider.solid);
+			let handle = self
+				.colliders
+				.insert(builder.build(), body.handle, &mut self.bodies);
+			body.collider = Some((handle, collider.clone(), transform.scale));
+		}
+	}
+}
+
+fn shape(shape: &Shape, transform: &Transform) -> ColliderBuilder {
+	let scale = Vector2::new(transform.scale[0], transform.scale[1]);
+	match shape {
+		Shape::Circle { radius } => {
+			ColliderBuilder::ball(radius * scale.x.abs().max(scale.y.abs()))
+		},
+		Shape::Aabb { half_extents } | Shape::Obb { half_extents } => ColliderBuilder::cuboid(
+			(half_extents.x() * scale.x).abs(),
+			(half_extents.y() * scale.y).abs(),
+		),
+		Shape::Polygon { points } => {
+			let points = points
+				.iter()
+				.map(|point| Point2::new(point.x() * scale.x, point.y() * scale.y))
+				.collect::<Vec<_>>();
+			ColliderBuilder::convex_hull(&points)
+				.unwrap_or_else(|| ColliderBuilder::ball(scale.x.abs().max(scale.y.abs())))
+		},
+	}
+}
+
+fn synced(transform: &Transform) -> (glam::Vec2, f32) {
+	(
+		glam::vec2(transform.position.x(), transform.position.y()),
+		transform.rotation.x(),
+	)
+}
+
+fn moved(from: (glam::Vec2, f32), to: (glam::Vec2, f32)) -> bool {
+	use std::f32::consts::{PI, TAU};
+	(to.0 - from.0).length() > EPSILON
+		|| ((to.1 - from.1 + PI).rem_euclid(TAU) - PI).abs() > EPSILON
+}
+
+
+// creates, rebuilds and removes bodies and copies the component state into them
+#[flame]
+pub fn sync(
+	transforms: View<Transform>, physics: View<Physics>, colliders: View<Collider>,
+	mut world: UniqueViewMut<RapierWorld>,
+) {
+	// bodies are rebuilt when the entity gained or lost its physics
+	let mut removed = world
+		.entities
+		.iter()
+		.filter(|(id, body)| {
+			(&transforms).get(**id).is_err()
+				|| body.dynamic != (&physics).get(**id).is_ok()
+				|| (!body.dynamic && (&colliders).get(**id).is_err())
+		})
+		.map(|(id, _)| *id)
+		.collect::<Vec<_>>();
+	// keeps the body arena deterministic
+	removed.sort_by_key(|id| id.index());
+	for id in removed {
+		world.remove(id);
+	}
+
+	for (id, transform) in (&transforms).iter().with_id() {
+		let physics = (&physics).get(id).ok();
+		let collider = (&colliders).get(id).ok();
+		if physics.is_none() && collider.is_none() {
+			continue;
+		}
+		if world.handle(id).is_none() {
+			world.insert(id, transform, physics);
+		}
+		world.update_collider(id, transform, collider);
+
+		let world = &mut *world;
+		let entity = world.entities.get_mut(&id).unwrap();
+		let body = &mut world.bodies[entity.handle];
+		// only positions written outside of the simulation since the last sync are pushed
+		if moved(entity.synced, synced(transform)) {
+			body.set_position(
+				Isometry2::new(
+					Vector2::new(transform.position.x(), transform.position.y()),
+					transform.rotation.x(),
+				),
+				true,
+			);
+			entity.synced = synced(transform);
+		}
+		if let Some(physics) = physics {
+			body.set_linvel(
+				Vector2::new(physics.velocity.x(), physics.velocity.y()),
+				true,
+			);
+			body.set_angvel(physics.angular_velocity, true);
+			body.linear_damping = physics.drag;
+			body.apply_force(
+				Vector2::new(physics.acceleration.x(), physics.acceleration.y())
+					* physics.mass.max(0.0),
+				true,
+			);
+		}
+	}
+}
+
+#[flame]
+pub fn step(mut world: UniqueViewMut<RapierWorld>, clock: UniqueView<Clock>) {
+	let world = &mut *world;
+	world.parameters.dt = clock.delta();
+	world.pipeline.step(
+		&Vector2::zeros(),
+		&world.parameters,
+		&mut world.broad_phase,
+		&mut world.narrow_phase,
+		&mut world.bodies,
+		&mut world.colliders,
+		&mut world.joints,
+		&mut world.ccd_solver,
+		&(),
+		&(),
+	);
+}
+
+// copies the simulated bodies back into the components
+#[flame]
+pub fn writeback(
+	mut transforms: ViewMut<Transform>, mut physics: ViewMut<Physics>, aims: View<Aim>,
+	mut world: UniqueViewMut<RapierWorld>,
+) {
+	let world = &mut *world;
+	for (id, (transform, physics)) in (&mut transforms, &mut physics).iter().with_id() {
+		let body = match world.entities.get(&id) {
+			Some(entity) if entity.dynamic => &world.bodies[entity.handle],
+			_ => continue,
+		};
+		let position = body.position();
+		*transform.position.x_mut() = position.translation.x;
+		*transform.position.y_mut() = position.translation.y;
+		physics.velocity = glam::Vec3::new(body.linvel().x, body.linvel().y, 0.0);
+		if let Some(max_speed) = physics.max_speed {
+			if physics.velocity.length() > max_speed {
+				physics.velocity = physics.velocity.normalize() * max_speed;
+			}
+		}
+		physics.angular_velocity = body.angvel();
+		physics.acceleration = glam::Vec3::zero();
+
+		if physics.angular_velocity != 0.0 {
+			transform.rotation = glam::Vec3::new(position.rotation.angle(), 0.0, 0.0);
+		} else if (&aims).get(id).map_or(true, |aim| aim.direction.is_none()) {
+			face(transform, physics.velocity);
+		}
+		// facing is written by the simulation too, so it doesn't count as a teleport
+		world.entities.get_mut(&id).unwrap().synced = synced(transform);
+	}
+}
@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Error};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::VecDeque,
+	fs::File,
+	io::{BufReader, BufWriter, ErrorKind, Write},
+	path::Path,
+};
+
+use crate::{actions::Bindings, input::Input, util::create_version_string};
+
+
+const MAGIC: [u8; 4] = *b"MREP";
+const FORMAT: u32 = 1;
+const KEYFRAME_INTERVAL: u64 = 60;
+
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+	magic: [u8; 4],
+	format: u32,
+	version: String,
+	seed: u64,
+	tick_rate: f32,
+	bindings: Bindings,
+}
+
+// frames are only written when the input changes and periodically as keyframes
+#[derive(Serialize, Deserialize)]
+struct Frame {
+	tick: u64,
+	input: Option<Input>,
+}
+
+
+pub struct Recorder {
+	writer: BufWriter<File>,
+	last: Option<Input>,
+	last_tick: u64,
+	tick: u64,
+}
+
+impl Recorder {
+	pub fn create(
+		path: &Path, seed: u64, tick_rate: f32, bindings: &Bindings,
+	) -> Result<Self, Error> {
+		let mut writer = BufWriter::new(File::create(path)?);
+		bincode::serialize_into(&mut writer, &Header {
+			magic: MAGIC,
+			format: FORMAT,
+			version: create_version_string(),
+			seed,
+			tick_rate,
+			bindings: bindings.clone(),
+		})?;
+		writer.flush()?;
+		Ok(Self {
+			writer,
+			last: None,
+			last_tick: 0,
+			tick: 0,
+		})
+	}
+
+	pub fn record(&mut self, tick: u64, input: &Input) -> Result<(), Error> {
+		self.tick = tick;
+		if self.last.as_ref() == Some(input)
+			&& tick.saturating_sub(self.last_tick) < KEYFRAME_INTERVAL
+		{
+			return Ok(());
+		}
+		bincode::serialize_into(&mut self.writer, &Frame {
+			tick,
+			input: Some(input.clone()),
+		})?;
+		// flushed on every change so the file stays usable after a crash
+		self.writer.flush()?;
+		self.last = Some(input.clone());
+		self.last_tick = tick;
+		Ok(())
+	}
+
+	pub fn finish(mut self) -> Result<(), Error> {
+		bincode::serialize_into(&mut self.writer, &Frame {
+			tick: self.tick,
+			input: None,
+		})?;
+		self.writer.flush()?;
+		Ok(())
+	}
+}
+
+
+pub struct Replay {
+	seed: u64,
+	bindings: Bindings,
+	frames: VecDeque<Frame>,
+	input: Input,
+	finished: bool,
+}
+
+impl Replay {
+	pub fn open(path: &Path, tick_rate: f32) -> Result<Self, Error> {
+		let mut reader = BufReader::new(File::open(path)?);
+		let header: Header = bincode::deserialize_from(&mut reader)?;
+		if header.magic != MAGIC {
+			return Err(anyhow!("{} is not a replay file", path.display()));
+		}
+		if header.format != FORMAT {
+			return Err(anyhow!(
+				"unsupported replay format {} (expected {})",
+				header.format,
+				FORMAT
+			));
+		}
+		if header.tick_rate != tick_rate {
+			return Err(anyhow!(
+				"replay was recorded at {} ticks per second (expected {})",
+				header.tick_rate,
+				tick_rate
+			));
+		}
+		if header.version != create_version_string() {
+			warn!(
+				"replay was recorded with {}, playback may diverge",
+				header.version
+			);
+		}
+
+		let mut frames = VecDeque::new();
+		loop {
+			match bincode::deserialize_from::<_, Frame>(&mut reader) {
+				Ok(frame) => frames.push_back(frame),
+				Err(error) => match *error {
+					bincode::ErrorKind::Io(ref io) if io.kind() == ErrorKind::UnexpectedEof => {
+						break
+					},
+					_ => return Err(error.into()),
+				},
+			}
+		}
+
+		Ok(Self {
+			seed: header.seed,
+			bindings: header.bindings,
+			frames,
+			input: Input::new(),
+			finished: false,
+		})
+	}
+
+	pub fn seed(&self) -> u64 { self.seed }
+
+	pub fn bindings(&self) -> &Bindings { &self.bindings }
+
+	pub fn is_finished(&self) -> bool { self.finished }
+
+	// recordings cut short by a crash end with the last keyframe instead of an end marker
+	pub fn input(&mut self, tick: u64) -> &Input {
+		while let Some(frame) = self.frames.front() {
+			if frame.tick > tick {
+				break;
+			}
+			if let Some(input) = self.frames.pop_front().and_then(|frame| frame.input) {
+				self.input = input;
+			}
+		}
+		self.finished = self.frames.is_empty();
+		&self.input
+	}
+}
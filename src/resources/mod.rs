@@ -2,10 +2,32 @@ pub mod shaders;
 
 // TODO: rewrite with baking support
 // TODO: rewrite whole hot reload logic to support both baked and unbaked loading
+#[cfg(feature = "hotreload")]
 pub fn get_file(file: &str) -> std::io::Result<Vec<u8>> {
 	std::fs::read(std::env::current_dir().unwrap().join("data").join(file))
 }
 
+#[cfg(not(feature = "hotreload"))]
+static FILES: &[(&str, &[u8])] = &[
+	("bindings.ron", include_bytes!("../../data/bindings.ron")),
+	("prefabs.ron", include_bytes!("../../data/prefabs.ron")),
+];
+#[cfg(not(feature = "hotreload"))]
+static SCENES: include_dir::Dir = include_dir::include_dir!("data/scenes");
+#[cfg(not(feature = "hotreload"))]
+pub fn get_file(file: &str) -> std::io::Result<Vec<u8>> {
+	let contents = match file.strip_prefix("scenes/") {
+		Some(scene) => SCENES.get_file(scene).map(|file| file.contents()),
+		None => FILES
+			.iter()
+			.find(|(name, _)| *name == file)
+			.map(|(_, contents)| *contents),
+	};
+	contents
+		.map(<[u8]>::to_vec)
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, file))
+}
+
 
 #[cfg(feature = "hotreload")]
 pub fn get_image(file: &str) -> image::ImageResult<image::DynamicImage> {
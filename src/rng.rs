@@ -0,0 +1,56 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::ops::{Deref, DerefMut};
+
+
+// enough to continue a sequence exactly where it was saved
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct RngState {
+	seed: u64,
+	stream: u64,
+	word_pos: u128,
+}
+
+pub struct Rng {
+	seed: u64,
+	rng: ChaCha8Rng,
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Self {
+		Self {
+			seed,
+			rng: ChaCha8Rng::seed_from_u64(seed),
+		}
+	}
+
+	pub fn seed(&self) -> u64 { self.seed }
+
+	// restarts the sequence from the initial seed
+	pub fn reseed(&mut self) { self.rng = ChaCha8Rng::seed_from_u64(self.seed); }
+
+	pub fn state(&self) -> RngState {
+		RngState {
+			seed: self.seed,
+			stream: self.rng.get_stream(),
+			word_pos: self.rng.get_word_pos(),
+		}
+	}
+
+	pub fn restore(&mut self, state: RngState) {
+		*self = Self::new(state.seed);
+		self.rng.set_stream(state.stream);
+		self.rng.set_word_pos(state.word_pos);
+	}
+}
+
+impl Deref for Rng {
+	type Target = ChaCha8Rng;
+
+	fn deref(&self) -> &Self::Target { &self.rng }
+}
+
+impl DerefMut for Rng {
+	fn deref_mut(&mut self) -> &mut Self::Target { &mut self.rng }
+}
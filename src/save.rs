@@ -0,0 +1,194 @@
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use shipyard::{
+	AllStoragesViewMut, EntitiesView, EntitiesViewMut, EntityId, IntoIter, Shiperator, UniqueView,
+	UniqueViewMut, View, ViewMut, World,
+};
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{BufReader, BufWriter},
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	components::*,
+	rng::{Rng, RngState},
+	session::Session,
+	time::Clock,
+	util::create_version_string,
+};
+
+
+const FORMAT: u32 = 1;
+
+
+macro_rules! saved_components {
+	($($field:ident: $component:ty),* $(,)?) => {
+		#[derive(Serialize, Deserialize)]
+		struct SavedEntity {
+			id: EntityId,
+			$($field: Option<$component>,)*
+		}
+
+		impl SavedEntity {
+			fn new(id: EntityId) -> Self {
+				Self {
+					id,
+					$($field: None,)*
+				}
+			}
+
+			fn collect(all_storages: &AllStoragesViewMut) -> Vec<Self> {
+				let mut entities: HashMap<EntityId, Self> = HashMap::new();
+				$(
+					all_storages.run(|storage: View<$component>| {
+						for (id, component) in (&storage).iter().with_id() {
+							entities.entry(id).or_insert_with(|| Self::new(id)).$field =
+								Some(component.clone());
+						}
+					});
+				)*
+				let mut entities: Vec<Self> = entities.into_iter().map(|(_, entity)| entity).collect();
+				entities.sort_by_key(|entity| entity.id.index());
+				entities
+			}
+
+			fn insert(self, all_storages: &AllStoragesViewMut, id: EntityId) {
+				$(
+					if let Some(component) = self.$field {
+						all_storages.run(|entities: EntitiesView, mut storage: ViewMut<$component>| {
+							entities.add_component(&mut storage, component, id);
+						});
+					}
+				)*
+			}
+		}
+	};
+}
+
+saved_components! {
+	transform: Transform,
+	sprite: Sprite,
+	enemy: Enemy,
+	player: Player,
+	life: Life,
+	physics: Physics,
+	weapon: Weapon,
+	selfdamage: SelfDamage,
+	contactdamage: ContactDamage,
+	collider: Collider,
+	faction: Faction,
+	owner: Owner,
+	spawner: Spawner,
+	aim: Aim,
+	camera: Camera,
+	camerafollow: CameraFollow,
+}
+
+impl SavedEntity {
+	// points entity references at the entities created on load, references to entities that
+	// weren't saved are dropped
+	fn remap(&mut self, ids: &HashMap<EntityId, EntityId>) {
+		if let Some(spawner) = &mut self.spawner {
+			spawner.player = spawner.player.and_then(|player| remap(player, ids));
+		}
+		self.owner = self
+			.owner
+			.take()
+			.and_then(|owner| remap(owner.entity, ids).map(|entity| Owner { entity }));
+		self.camerafollow = self.camerafollow.take().and_then(|camerafollow| {
+			remap(camerafollow.entity, ids).map(|entity| CameraFollow { entity })
+		});
+	}
+}
+
+fn remap(id: EntityId, ids: &HashMap<EntityId, EntityId>) -> Option<EntityId> {
+	ids.get(&id).copied()
+}
+
+
+#[derive(Serialize, Deserialize)]
+struct SaveGame {
+	format: u32,
+	version: String,
+	time: f32,
+	unscaled_time: f32,
+	ticks: u64,
+	rng: RngState,
+	session: Session,
+	camera: Option<EntityId>,
+	entities: Vec<SavedEntity>,
+}
+
+pub fn quicksave_path() -> Option<PathBuf> {
+	dirs::data_dir().map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("quicksave.sav"))
+}
+
+pub fn save(world: &World, path: &Path, camera: Option<EntityId>) -> Result<(), Error> {
+	let savegame = world.run(|all_storages: AllStoragesViewMut| {
+		let entities = SavedEntity::collect(&all_storages);
+		all_storages.run(
+			|clock: UniqueView<Clock>, rng: UniqueView<Rng>, session: UniqueView<Session>| {
+				SaveGame {
+					format: FORMAT,
+					version: create_version_string(),
+					time: clock.time(),
+					unscaled_time: clock.unscaled_time(),
+					ticks: clock.ticks(),
+					rng: rng.state(),
+					session: session.clone(),
+					camera,
+					entities,
+				}
+			},
+		)
+	});
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+	bincode::serialize_into(BufWriter::new(File::create(path)?), &savegame)?;
+	Ok(())
+}
+
+// replaces all entities in the world, returns the new id of the saved camera
+pub fn load(world: &World, path: &Path) -> Result<Option<EntityId>, Error> {
+	let savegame: SaveGame = bincode::deserialize_from(BufReader::new(File::open(path)?))?;
+	if savegame.format != FORMAT {
+		return Err(anyhow!(
+			"unsupported save format {} (expected {})",
+			savegame.format,
+			FORMAT
+		));
+	}
+
+	world.run(|mut all_storages: AllStoragesViewMut| {
+		all_storages.clear();
+
+		let ids = all_storages.run(|mut entities: EntitiesViewMut| {
+			savegame
+				.entities
+				.iter()
+				.map(|entity| (entity.id, entities.add_entity((), ())))
+				.collect::<HashMap<_, _>>()
+		});
+		for mut entity in savegame.entities {
+			entity.remap(&ids);
+			let id = ids[&entity.id];
+			entity.insert(&all_storages, id);
+		}
+
+		all_storages.run(
+			|mut clock: UniqueViewMut<Clock>,
+			 mut rng: UniqueViewMut<Rng>,
+			 mut session: UniqueViewMut<Session>| {
+				clock.set_time(savegame.time, savegame.unscaled_time);
+				clock.set_ticks(savegame.ticks);
+				rng.restore(savegame.rng);
+				*session = savegame.session;
+			},
+		);
+
+		Ok(savegame.camera.and_then(|camera| remap(camera, &ids)))
+	})
+}
@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use shipyard::{
+	AllStoragesViewMut, EntitiesView, EntitiesViewMut, EntityId, UniqueView, ViewMut, World,
+};
+use std::collections::HashMap;
+
+use crate::{components::*, prefab::Prefabs, resources};
+
+
+// entities are referenced by name in data files and by id when spawned from code
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EntityRef {
+	Name(String),
+	#[serde(skip)]
+	Id(EntityId),
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SceneComponent {
+	Transform(Transform),
+	Sprite(Sprite),
+	Enemy,
+	Player(Player),
+	Life(Life),
+	Physics(Physics),
+	Weapon(Weapon),
+	SelfDamage(SelfDamage),
+	ContactDamage(ContactDamage),
+	Collider(Collider),
+	Faction(Faction),
+	Owner {
+		entity: EntityRef,
+	},
+	Spawner {
+		spawnrate: f32,
+		player: EntityRef,
+		#[serde(default)]
+		prefabs: Vec<String>,
+	},
+	Aim,
+	Paused,
+	Camera(Camera),
+	CameraFollow {
+		entity: EntityRef,
+	},
+	// patches the position or rotation of the transform when merged into a prefab
+	Position(glam::Vec3),
+	Rotation(glam::Vec3),
+}
+
+impl SceneComponent {
+	pub fn insert(
+		&self, all_storages: &AllStoragesViewMut, id: EntityId, names: &HashMap<String, EntityId>,
+	) -> Result<(), Error> {
+		match self {
+			SceneComponent::Transform(transform) => add(all_storages, id, transform.clone()),
+			SceneComponent::Sprite(sprite) => add(all_storages, id, sprite.clone()),
+			SceneComponent::Enemy => add(all_storages, id, Enemy {}),
+			SceneComponent::Player(player) => add(all_storages, id, player.clone()),
+			SceneComponent::Life(life) => add(all_storages, id, life.clone()),
+			SceneComponent::Physics(physics) => add(all_storages, id, physics.clone()),
+			SceneComponent::Weapon(weapon) => add(all_storages, id, weapon.clone()),
+			SceneComponent::SelfDamage(selfdamage) => add(all_storages, id, selfdamage.clone()),
+			SceneComponent::ContactDamage(contactdamage) => {
+				add(all_storages, id, contactdamage.clone())
+			},
+			SceneComponent::Collider(collider) => add(all_storages, id, collider.clone()),
+			SceneComponent::Faction(faction) => add(all_storages, id, *faction),
+			SceneComponent::Owner { entity } => add(all_storages, id, Owner {
+				entity: resolve(names, entity)?,
+			}),
+			SceneComponent::Spawner {
+				spawnrate,
+				player,
+				prefabs,
+			} => add(all_storages, id, Spawner {
+				cooldown: Cooldown::new(*spawnrate),
+				player: Some(resolve(names, player)?),
+				prefabs: prefabs.clone(),
+			}),
+			SceneComponent::Aim => add(all_storages, id, Aim::default()),
+			SceneComponent::Paused => add(all_storages, id, Paused {}),
+			SceneComponent::Camera(camera) => add(all_storages, id, camera.clone()),
+			SceneComponent::CameraFollow { entity } => add(all_storages, id, CameraFollow {
+				entity: resolve(names, entity)?,
+			}),
+			SceneComponent::Position(_) | SceneComponent::Rotation(_) => (),
+		}
+		Ok(())
+	}
+}
+
+fn add<T: 'static + Send + Sync>(all_storages: &AllStoragesViewMut, id: EntityId, component: T) {
+	all_storages.run(|entities: EntitiesView, mut storage: ViewMut<T>| {
+		entities.add_component(&mut storage, component, id);
+	});
+}
+
+fn resolve(names: &HashMap<String, EntityId>, entity: &EntityRef) -> Result<EntityId, Error> {
+	match entity {
+		EntityRef::Name(name) => names
+			.get(name)
+			.copied()
+			.ok_or_else(|| anyhow!("unknown entity: {}", name)),
+		EntityRef::Id(id) => Ok(*id),
+	}
+}
+
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneEntity {
+	#[serde(default)]
+	pub name: Option<String>,
+	#[serde(default)]
+	pub prefab: Option<String>,
+	#[serde(default)]
+	pub components: Vec<SceneComponent>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Scene {
+	#[serde(default)]
+	pub camera: Option<EntityRef>,
+	pub entities: Vec<SceneEntity>,
+}
+
+pub struct SceneInstance {
+	pub names: HashMap<String, EntityId>,
+	pub camera: Option<EntityId>,
+}
+
+impl Scene {
+	pub fn load(name: &str) -> Result<Self, Error> {
+		Ok(ron::de::from_bytes(&resources::get_file(
+			&["scenes/", name, ".ron"].concat(),
+		)?)?)
+	}
+
+	pub fn instantiate(&self, world: &World) -> Result<SceneInstance, Error> {
+		world.run(|all_storages: AllStoragesViewMut| {
+			let components = all_storages.run(|prefabs: UniqueView<Prefabs>| {
+				self.entities
+					.iter()
+					.map(|entity| prefabs.build(entity.prefab.as_deref(), &entity.components))
+					.collect::<Result<Vec<_>, _>>()
+			})?;
+			let ids = all_storages.run(|mut entities: EntitiesViewMut| {
+				self.entities
+					.iter()
+					.map(|_| entities.add_entity((), ()))
+					.collect::<Vec<_>>()
+			});
+			let names = self
+				.entities
+				.iter()
+				.zip(&ids)
+				.filter_map(|(entity, id)| entity.name.clone().map(|name| (name, *id)))
+				.collect::<HashMap<_, _>>();
+			for (components, id) in components.iter().zip(&ids) {
+				for component in components {
+					component.insert(&all_storages, *id, &names)?;
+				}
+			}
+			let camera = match &self.camera {
+				Some(camera) => Some(resolve(&names, camera)?),
+				None => None,
+			};
+			Ok(SceneInstance { names, camera })
+		})
+	}
+}
@@ -0,0 +1,133 @@
+use log::warn;
+use shipyard::{AllStoragesViewMut, UniqueViewMut};
+use std::{cmp::Ordering, collections::HashSet};
+
+use crate::events::Events;
+
+
+pub type TaskId = u64;
+
+pub type Callback = Box<dyn FnMut(&AllStoragesViewMut) + Send + Sync>;
+
+pub struct Task {
+	id: TaskId,
+	due: f32,
+	interval: Option<f32>,
+	callback: Callback,
+}
+
+// delayed and repeating callbacks on game time, so they stop while paused and follow the time scale
+pub struct Scheduler {
+	tasks: Vec<Task>,
+	cancelled: HashSet<TaskId>,
+	next_id: TaskId,
+	now: f32,
+}
+
+impl Scheduler {
+	pub fn new() -> Self {
+		Self {
+			tasks: Vec::new(),
+			cancelled: HashSet::new(),
+			next_id: 0,
+			now: 0.0,
+		}
+	}
+
+	pub fn after(
+		&mut self, delay: f32, callback: impl FnMut(&AllStoragesViewMut) + Send + Sync + 'static,
+	) -> TaskId {
+		self.add(delay, None, Box::new(callback))
+	}
+
+	pub fn every(
+		&mut self, interval: f32, callback: impl FnMut(&AllStoragesViewMut) + Send + Sync + 'static,
+	) -> TaskId {
+		self.add(interval, Some(interval), Box::new(callback))
+	}
+
+	pub fn send_after<T: Clone + Send + Sync + 'static>(&mut self, delay: f32, event: T) -> TaskId {
+		self.after(delay, move |all_storages| {
+			all_storages.run(|mut events: UniqueViewMut<Events<T>>| events.send(event.clone()))
+		})
+	}
+
+	pub fn send_every<T: Clone + Send + Sync + 'static>(
+		&mut self, interval: f32, event: T,
+	) -> TaskId {
+		self.every(interval, move |all_storages| {
+			all_storages.run(|mut events: UniqueViewMut<Events<T>>| events.send(event.clone()))
+		})
+	}
+
+	// tasks that are currently running are cancelled once they return
+	pub fn cancel(&mut self, id: TaskId) {
+		let len = self.tasks.len();
+		self.tasks.retain(|task| task.id != id);
+		if self.tasks.len() == len {
+			self.cancelled.insert(id);
+		}
+	}
+
+	pub fn is_scheduled(&self, id: TaskId) -> bool { self.tasks.iter().any(|task| task.id == id) }
+
+	pub fn clear(&mut self) {
+		self.tasks.clear();
+		self.cancelled.clear();
+	}
+
+	// delays are relative to this time, it has to follow the clock when the game time jumps
+	pub fn set_time(&mut self, now: f32) { self.now = now; }
+
+	// removes and returns the tasks due at the given time in the order they were due
+	pub fn take_due(&mut self, now: f32) -> Vec<Task> {
+		self.now = now;
+		let (mut due, pending) = self
+			.tasks
+			.drain(..)
+			.partition::<Vec<_>, _>(|task| task.due <= now);
+		self.tasks = pending;
+		due.sort_by(|a, b| {
+			a.due
+				.partial_cmp(&b.due)
+				.unwrap_or(Ordering::Equal)
+				.then(a.id.cmp(&b.id))
+		});
+		due
+	}
+
+	// puts repeating tasks back after they ran
+	pub fn requeue(&mut self, tasks: Vec<Task>) {
+		for mut task in tasks {
+			if self.cancelled.contains(&task.id) {
+				continue;
+			}
+			if let Some(interval) = task.interval {
+				task.due += interval;
+				self.tasks.push(task);
+			}
+		}
+		self.cancelled.clear();
+	}
+
+	fn add(&mut self, delay: f32, interval: Option<f32>, callback: Callback) -> TaskId {
+		let id = self.next_id;
+		self.next_id += 1;
+		// e.g. from data files, such a task would never be due
+		if !delay.is_finite() || interval.map_or(false, |interval| !interval.is_finite()) {
+			warn!("not scheduling task {} with a delay of {}", id, delay);
+			return id;
+		}
+		self.tasks.push(Task {
+			id,
+			due: self.now + delay,
+			interval,
+			callback,
+		});
+		id
+	}
+}
+
+impl Task {
+	pub fn run(&mut self, all_storages: &AllStoragesViewMut) { (self.callback)(all_storages); }
+}
@@ -1,3 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Session {
 	pub score: i32,
 }
@@ -0,0 +1,107 @@
+use glam::Vec2;
+use std::collections::HashMap;
+
+
+#[derive(Clone, Copy)]
+struct Entry<T> {
+	id: T,
+	position: Vec2,
+	radius: f32,
+}
+
+// uniform grid of circles for broadphase queries, entries are stored in every cell their bounds touch
+pub struct SpatialHash<T> {
+	cell_size: f32,
+	cells: HashMap<(i32, i32), Vec<usize>>,
+	entries: Vec<Entry<T>>,
+}
+
+impl<T: Copy> SpatialHash<T> {
+	pub fn new(cell_size: f32) -> Self {
+		Self {
+			cell_size,
+			cells: HashMap::new(),
+			entries: Vec::new(),
+		}
+	}
+
+	// keeps the cells that were used last time around since the hash is rebuilt every tick,
+	// cells that stayed empty are dropped so the map doesn't grow with everything ever visited
+	pub fn clear(&mut self) {
+		self.cells.retain(|_, cell| {
+			let keep = !cell.is_empty();
+			cell.clear();
+			keep
+		});
+		self.entries.clear();
+	}
+
+	pub fn len(&self) -> usize { self.entries.len() }
+
+	pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+	pub fn insert(&mut self, id: T, position: Vec2, radius: f32) {
+		let index = self.entries.len();
+		self.entries.push(Entry {
+			id,
+			position,
+			radius,
+		});
+		let extent = Vec2::new(radius, radius);
+		let (min, max) = (self.cell(position - extent), self.cell(position + extent));
+		for x in min.0..=max.0 {
+			for y in min.1..=max.1 {
+				self.cells.entry((x, y)).or_default().push(index);
+			}
+		}
+	}
+
+	// entries whose circle overlaps the given circle
+	pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<T> {
+		let extent = Vec2::new(radius, radius);
+		self.candidates(center - extent, center + extent)
+			.into_iter()
+			.map(|index| &self.entries[index])
+			.filter(|entry| (entry.position - center).length() < radius + entry.radius)
+			.map(|entry| entry.id)
+			.collect()
+	}
+
+	// entries whose bounds overlap the given rectangle
+	pub fn query_rect(&self, min: Vec2, max: Vec2) -> Vec<T> {
+		self.candidates(min, max)
+			.into_iter()
+			.map(|index| &self.entries[index])
+			.filter(|entry| {
+				entry.position.x() + entry.radius >= min.x()
+					&& entry.position.x() - entry.radius <= max.x()
+					&& entry.position.y() + entry.radius >= min.y()
+					&& entry.position.y() - entry.radius <= max.y()
+			})
+			.map(|entry| entry.id)
+			.collect()
+	}
+
+	// indices in insertion order so results don't depend on the hash map layout
+	fn candidates(&self, min: Vec2, max: Vec2) -> Vec<usize> {
+		let (min, max) = (self.cell(min), self.cell(max));
+		let mut candidates = Vec::new();
+		for x in min.0..=max.0 {
+			for y in min.1..=max.1 {
+				if let Some(cell) = self.cells.get(&(x, y)) {
+					candidates.extend_from_slice(cell);
+				}
+			}
+		}
+		candidates.sort_unstable();
+		candidates.dedup();
+		candidates
+	}
+
+	fn cell(&self, position: Vec2) -> (i32, i32) {
+		(
+			(position.x() / self.cell_size).floor() as i32,
+			(position.y() / self.cell_size).floor() as i32,
+		)
+	}
+}
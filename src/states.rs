@@ -1,14 +1,52 @@
-use crate::{components::*, graphics::Renderer, universe::Universe};
-use shipyard::{EntitiesViewMut, UniqueViewMut, ViewMut};
+use log::error;
+use shipyard::{
+	AllStoragesViewMut, EntityId, IntoIter, Shiperator, UniqueView, UniqueViewMut, View,
+};
 use winit::event::Event;
 
+use crate::{
+	actions::{Action, Actions},
+	components::Camera,
+	input::Input,
+	scene::SceneComponent,
+	time::Clock,
+	universe::{Transition, Universe},
+	workloads::{self, Workloads},
+};
+
 pub trait State {
 	fn new(universe: &Universe) -> Self
 	where
 		Self: Sized;
+
+	// name of the scene instantiated before init
+	fn scene(&self) -> Option<&'static str> { None }
+
 	fn init(&mut self, universe: &Universe);
-	fn event(&mut self, universe: &Universe, event: Event<()>);
+
+	fn workloads(&self) -> Workloads { Workloads::default() }
+	// returns whether the event was consumed and should not reach states below or the input resource
+	fn event(&mut self, universe: &Universe, event: &Event<()>) -> bool;
+	// called once per fixed tick after the input for the tick is known
+	fn tick(&mut self, _universe: &Universe) {}
 	fn update(&mut self, universe: &Universe);
+
+	fn render(&mut self, _universe: &Universe) {}
+
+	// called when another state is pushed on top of this one
+	fn on_pause(&mut self, _universe: &Universe) {}
+
+	// called when this state becomes the top state again
+	fn on_resume(&mut self, _universe: &Universe) {}
+
+	// called when this state is popped, replaced or switched away from
+	fn on_exit(&mut self, _universe: &Universe) {}
+
+	// whether states below this one keep rendering
+	fn is_overlay(&self) -> bool { false }
+
+	// whether states below this one are frozen
+	fn is_blocking(&self) -> bool { true }
 }
 
 
@@ -18,7 +56,7 @@ impl State for EmptyState {
 
 	fn init(&mut self, _universe: &Universe) {}
 
-	fn event(&mut self, _universe: &Universe, _event: Event<()>) {}
+	fn event(&mut self, _universe: &Universe, _event: &Event<()>) -> bool { false }
 
 	fn update(&mut self, _universe: &Universe) {}
 }
@@ -28,84 +66,94 @@ pub struct SpaceShooterState {}
 impl State for SpaceShooterState {
 	fn new(_universe: &Universe) -> Self { Self {} }
 
-	fn init(&mut self, universe: &Universe) {
-		let camera = universe.world.run(
-			|mut entities: EntitiesViewMut,
-			 mut transforms: ViewMut<Transform>,
-			 mut players: ViewMut<Player>,
-			 mut spawners: ViewMut<Spawner>,
-			 mut physics: ViewMut<Physics>,
-			 mut weapons: ViewMut<Weapon>,
-			 mut healths: ViewMut<Life>,
-			 mut cameras: ViewMut<Camera>,
-			 mut camerafollow: ViewMut<CameraFollow>,
-			 mut sprites: ViewMut<Sprite>| {
-				let player = entities.add_entity(
-					(
-						&mut transforms,
-						&mut sprites,
-						&mut players,
-						&mut healths,
-						&mut physics,
-						&mut weapons,
-					),
-					(
-						Transform {
-							position: glam::Vec3::new(0.5, 0.5, 10.0),
-							scale: [0.35, 0.35],
-							rotation: glam::Vec3::new(0.0, 0.0, 0.0),
-						},
-						Sprite {
-							color: [0.1, 0.4, 1.0, 0.0],
-							sprite: [47.0, 1.0],
-						},
-						Player {},
-						Life { health: 10.0 },
-						Physics {
-							acceleration: glam::Vec3::zero(),
-							deceleration: 1.5,
-						},
-						Weapon {
-							repeat: 0.2,
-							last: 0.0,
-						},
-					),
-				);
-				entities.add_entity(
-					(&mut spawners,),
-					(Spawner {
-						spawnrate: 0.2,
-						last: 0.0,
-						player,
-					},),
-				);
-
-				entities.add_entity(
-					(&mut cameras, &mut camerafollow),
+	fn scene(&self) -> Option<&'static str> { Some("spaceshooter") }
+
+	fn workloads(&self) -> Workloads {
+		Workloads {
+			fixed: Some(workloads::SHOOTER_FIXED),
+			update: Some(workloads::SHOOTER_UPDATE),
+			render: Some(workloads::SHOOTER_RENDER),
+		}
+	}
+
+	fn init(&mut self, _universe: &Universe) {}
+
+	fn event(&mut self, _universe: &Universe, _event: &Event<()>) -> bool { false }
+
+	fn tick(&mut self, universe: &Universe) {
+		let (pause, quicksave, quickload) =
+			universe
+				.world
+				.run(|actions: UniqueView<Actions>, input: UniqueView<Input>| {
 					(
-						Camera {
-							// +z is out of the screen
-							eye: (0.0, 0.0, 0.0).into(),
-							// have it look at the origin
-							target: (0.0, 0.0, 100.0).into(),
-							// which way is "up"
-							up: glam::Vec3::unit_y(),
-							aspect: 1.0,
-							fovy: 90.0,
-							znear: 0.1,
-							zfar: 100.0,
-						},
-						CameraFollow { entity: player },
-					),
-				)
-			},
-		);
+						actions.any_just_pressed(Action::Pause) || input.pause_requested,
+						actions.any_just_pressed(Action::Quicksave),
+						actions.any_just_pressed(Action::Quickload),
+					)
+				});
+		if quicksave {
+			universe.quicksave();
+		}
+		if quickload {
+			universe.quickload();
+		}
+		if pause {
+			universe.transition(Transition::push::<PauseState>());
+		}
+	}
+
+	fn update(&mut self, _universe: &Universe) {}
+}
+
+
+// freezes the states below while still rendering them, with a marker in front of the camera
+pub struct PauseState {
+	overlay: Option<EntityId>,
+}
+impl State for PauseState {
+	fn new(_universe: &Universe) -> Self { Self { overlay: None } }
+
+	fn init(&mut self, universe: &Universe) {
 		universe
 			.world
-			.run(|mut renderer: UniqueViewMut<Renderer>| renderer.camera = Some(camera));
+			.run(|mut clock: UniqueViewMut<Clock>| clock.pause());
+		let eye = universe
+			.world
+			.run(|cameras: View<Camera>| (&cameras).iter().next().map(|camera| camera.eye))
+			.unwrap_or_else(glam::Vec3::zero);
+		match universe.spawn_prefab("paused", &[SceneComponent::Position(glam::Vec3::new(
+			eye.x(),
+			eye.y(),
+			9.0,
+		))]) {
+			Ok(id) => self.overlay = Some(id),
+			Err(error) => error!("couldn't spawn pause overlay: {}", error),
+		}
 	}
 
-	fn event(&mut self, _universe: &Universe, _event: Event<()>) {}
+	fn event(&mut self, _universe: &Universe, _event: &Event<()>) -> bool { false }
+
+	fn tick(&mut self, universe: &Universe) {
+		if universe
+			.world
+			.run(|actions: UniqueView<Actions>| actions.any_just_pressed(Action::Pause))
+		{
+			universe.transition(Transition::Pop);
+		}
+	}
 
 	fn update(&mut self, _universe: &Universe) {}
+
+	fn on_exit(&mut self, universe: &Universe) {
+		universe
+			.world
+			.run(|mut clock: UniqueViewMut<Clock>| clock.resume());
+		if let Some(id) = self.overlay.take() {
+			universe
+				.world
+				.run(|mut all_storages: AllStoragesViewMut| all_storages.delete(id));
+		}
+	}
+
+	fn is_overlay(&self) -> bool { true }
 }
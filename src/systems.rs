@@ -1,27 +1,91 @@
 #![allow(clippy::too_many_arguments)]
 
 use flamer::flame;
+use log::error;
 use shipyard::{
-	AllStoragesViewMut, EntitiesViewMut, Get, IntoIter, Shiperator, UniqueView, UniqueViewMut,
-	View, ViewMut,
+	AllStoragesViewMut, Delete, EntitiesView, EntityId, Get, IntoIter, Shiperator, UniqueView,
+	UniqueViewMut, View, ViewMut,
 };
-use winit::event::VirtualKeyCode;
 use zerocopy::AsBytes;
 
 use crate::{
+	actions::{Action, Actions},
+	collision::{intersect_swept, Contacts, WorldShape},
 	components::*,
+	events::*,
 	graphics::{get_aligned, get_buffer_size, BackgroundArgs, CameraArgs, Renderer, SpriteArgs},
 	input::Input,
+	prefab::spawn_prefab,
+	rng::Rng,
+	scene::{EntityRef, SceneComponent},
+	scheduler::Scheduler,
 	session::Session,
-	time::Timer,
+	spatial::SpatialHash,
+	time::Clock,
 };
-use rand::Rng;
+use rand::Rng as _;
 
 
 const ACCELERATION: f32 = 20.0;
-const MAXSPEED: f32 = 16.0;
+const MUZZLE_IMPULSE: f32 = 1.0;
+const HIT_STOP: f32 = 0.06;
 
 
+// aims toward the right stick when it is held and toward the cursor otherwise, following cameras
+// are unprojected at their settled pose so the aim only depends on the tick state
+#[flame]
+pub fn aim(
+	players: View<Player>, mut transforms: ViewMut<Transform>, mut aims: ViewMut<Aim>,
+	cameras: View<Camera>, camerafollow: View<CameraFollow>, input: UniqueView<Input>,
+	actions: UniqueView<Actions>,
+) {
+	let poses = (&cameras)
+		.iter()
+		.with_id()
+		.map(|(id, camera)| {
+			let follow = (&camerafollow).get(id).ok().map(|follow| follow.entity);
+			let pose = follow
+				.and_then(|entity| (&transforms).get(entity).ok())
+				.map(|transform| camera.following(transform.position))
+				.unwrap_or_else(|| camera.clone());
+			(follow, pose)
+		})
+		.collect::<Vec<_>>();
+	for (id, (player, transform, aim)) in (&players, &mut transforms, &mut aims).iter().with_id() {
+		let stick = glam::Vec3::new(
+			actions.value(player.index, Action::AimX),
+			actions.value(player.index, Action::AimY),
+			0.0,
+		);
+		let cursor = if player.index == 0 {
+			input.cursor
+		} else {
+			None
+		};
+		aim.direction = if stick.length() > 0.0 {
+			Some(stick.normalize())
+		} else if let Some(cursor) = cursor {
+			let camera = poses
+				.iter()
+				.rev()
+				.find(|(follow, _)| *follow == Some(id))
+				.or_else(|| poses.first())
+				.map(|(_, pose)| pose);
+			camera
+				.and_then(|camera| camera.unproject(cursor, input.viewport, transform.position.z()))
+				.map(|target| target - transform.position)
+				.filter(|direction| direction.length() > 0.0)
+				.map(|direction| direction.normalize())
+		} else {
+			None
+		};
+		if let Some(direction) = aim.direction {
+			transform.rotation =
+				glam::Vec3::new(-f32::atan2(direction.x(), direction.y()), 0.0, 0.0);
+		}
+	}
+}
+
 #[flame]
 pub fn input(all_storages: AllStoragesViewMut) {
 	let mut adds = Vec::new();
@@ -30,179 +94,327 @@ pub fn input(all_storages: AllStoragesViewMut) {
 		 mut weapons: ViewMut<Weapon>,
 		 mut transforms: ViewMut<Transform>,
 		 mut physics: ViewMut<Physics>,
-		 timer: UniqueView<Timer>,
-		 input: UniqueView<Input>| {
+		 clock: UniqueView<Clock>,
+		 actions: UniqueView<Actions>| {
 			(&mut players, &mut physics, &mut transforms, &mut weapons)
 				.iter()
-				.for_each(|(_player, physic, transform, weapon)| {
-					use VirtualKeyCode::*;
-					for key in &input.keys_down {
-						match key {
-							Up => {
-								physic.acceleration +=
-									glam::Vec3::new(0.0, ACCELERATION * 1.5, 0.0) * timer.delta();
-							},
-							Down => {
-								physic.acceleration +=
-									glam::Vec3::new(0.0, -ACCELERATION * 1.5, 0.0) * timer.delta();
-							},
-							Right => {
-								physic.acceleration +=
-									glam::Vec3::new(ACCELERATION * 1.5, 0.0, 0.0) * timer.delta();
-							},
-							Left => {
-								physic.acceleration +=
-									glam::Vec3::new(-ACCELERATION * 1.5, 0.0, 0.0) * timer.delta();
-							},
-							Space => {
-								if weapon.last + weapon.repeat < timer.lifetime() {
-									adds.push((
-										Transform {
-											position: transform.position
-												+ glam::Vec3::new(
-													-transform.rotation.x().sin(),
-													transform.rotation.x().cos(),
-													0.0,
-												) * 0.8,
-											scale: [0.2, 0.2],
-											rotation: transform.rotation,
-										},
-										Physics {
-											acceleration: glam::Vec3::new(
-												-transform.rotation.x().sin(),
-												transform.rotation.x().cos(),
-												0.0,
-											) * 10.0 + physic.acceleration,
-											deceleration: 0.05,
-										},
-										Sprite {
-											color: [0.1, 0.4, 1.0, 0.0],
-											sprite: [1.0, 1.0],
-										},
-										SelfDamage { damage: 1.0 },
-										Life { health: 3.0 },
-										ContactDamage {
-											damage: 10.0,
-											once: true,
-										},
-									));
-									weapon.last = timer.lifetime();
-								}
-							},
-							_ => (),
-						}
+				.with_id()
+				.for_each(|(id, (player, physic, transform, weapon))| {
+					let movement = glam::Vec3::new(
+						actions.value(player.index, Action::MoveX),
+						actions.value(player.index, Action::MoveY),
+						0.0,
+					);
+					physic.apply_force(movement * ACCELERATION * 1.5 * physic.mass);
+					let fire = if weapon.automatic {
+						actions.pressed(player.index, Action::Fire)
+					} else {
+						actions.just_pressed(player.index, Action::Fire)
+					};
+					if fire && weapon.cooldown.try_trigger(clock.time()) {
+						let direction = glam::Vec3::new(
+							-transform.rotation.x().sin(),
+							transform.rotation.x().cos(),
+							0.0,
+						);
+						adds.push((
+							id,
+							transform.position + direction * 0.8,
+							transform.rotation,
+							physic.velocity,
+							direction * MUZZLE_IMPULSE,
+						));
 					}
 				});
 		},
 	);
+	for (player, position, rotation, velocity, impulse) in adds {
+		match spawn_prefab(&all_storages, "bullet", &[
+			SceneComponent::Position(position),
+			SceneComponent::Rotation(rotation),
+			SceneComponent::Owner {
+				entity: EntityRef::Id(player),
+			},
+		]) {
+			Ok(id) => all_storages.run(
+				|mut physics: ViewMut<Physics>,
+				 mut fired: UniqueViewMut<Events<ProjectileFired>>| {
+					// bullets inherit the velocity of the shooter
+					if let Ok(physics) = (&mut physics).get(id) {
+						physics.velocity = velocity;
+						physics.apply_impulse(impulse);
+					}
+					fired.send(ProjectileFired {
+						entity: id,
+						owner: player,
+					});
+				},
+			),
+			Err(error) => error!("couldn't spawn bullet: {}", error),
+		}
+	}
+}
+
+#[flame]
+pub fn spawn(all_storages: AllStoragesViewMut) {
+	let mut adds = Vec::new();
 	all_storages.run(
-		|mut entities: EntitiesViewMut,
-		 mut sprites: ViewMut<Sprite>,
-		 mut selfdamages: ViewMut<SelfDamage>,
-		 mut lifes: ViewMut<Life>,
-		 mut transforms: ViewMut<Transform>,
-		 mut physics: ViewMut<Physics>,
-		 mut contactdamages: ViewMut<ContactDamage>| {
-			for add in adds {
-				entities.add_entity(
-					(
-						&mut transforms,
-						&mut physics,
-						&mut sprites,
-						&mut selfdamages,
-						&mut lifes,
-						&mut contactdamages,
-					),
-					add,
-				);
-			}
+		|transforms: View<Transform>,
+		 mut spawners: ViewMut<Spawner>,
+		 clock: UniqueView<Clock>,
+		 mut rng: UniqueViewMut<Rng>| {
+			(&mut spawners).iter().for_each(|spawner| {
+				if spawner.cooldown.is_ready(clock.time()) && !spawner.prefabs.is_empty() {
+					let ppos = spawner
+						.player
+						.and_then(|player| (&transforms).get(player).ok())
+						.map(|t| t.position)
+						.unwrap_or_else(|| glam::Vec3::new(0.0, 0.0, 0.0));
+					let position = glam::Vec3::new(
+						ppos.x() + rng.gen_range(-10.0, 10.0),
+						ppos.y() + rng.gen_range(-10.0, 10.0),
+						10.0,
+					);
+					let prefab = spawner.prefabs[rng.gen_range(0, spawner.prefabs.len())].clone();
+					adds.push((prefab, position));
+					let rate = spawner.cooldown.duration;
+					let delay = rng.gen_range(rate / 2.0, rate * 2.0);
+					spawner.cooldown.trigger_for(clock.time(), delay);
+				}
+			});
 		},
 	);
+	for (prefab, position) in adds {
+		if let Err(error) = spawn_prefab(&all_storages, &prefab, &[SceneComponent::Position(
+			position,
+		)]) {
+			error!("couldn't spawn {}: {}", prefab, error);
+		}
+	}
 }
 
 #[flame]
-pub fn spawn(
-	mut entities: EntitiesViewMut, mut enemies: ViewMut<Enemy>, mut transforms: ViewMut<Transform>,
-	mut sprites: ViewMut<Sprite>, mut lifes: ViewMut<Life>, mut physics: ViewMut<Physics>,
-	mut contactdamages: ViewMut<ContactDamage>, mut spawners: ViewMut<Spawner>,
-	timer: UniqueView<Timer>,
+pub fn broadphase(
+	entities: EntitiesView, transforms: View<Transform>, colliders: View<Collider>,
+	physics: View<Physics>, mut shapes: ViewMut<WorldShape>,
+	mut spatial: UniqueViewMut<SpatialHash<EntityId>>, clock: UniqueView<Clock>,
 ) {
-	(&mut spawners).iter().for_each(|spawner| {
-		if spawner.last < timer.lifetime() {
-			let ppos = (&transforms)
-				.get(spawner.player)
-				.map(|t| t.position)
-				.unwrap_or_else(|_| glam::Vec3::new(0.0, 0.0, 0.0));
-			entities.add_entity(
-				(
-					&mut enemies,
-					&mut transforms,
-					&mut sprites,
-					&mut lifes,
-					&mut physics,
-					&mut contactdamages,
-				),
-				(
-					Enemy {},
-					Transform {
-						position: glam::Vec3::new(
-							ppos.x() + rand::thread_rng().gen_range(-10.0, 10.0),
-							ppos.y() + rand::thread_rng().gen_range(-10.0, 10.0),
-							10.0,
-						),
-						scale: [0.5, 0.5],
-						rotation: glam::Vec3::new(0.0, 0.0, 0.0),
-					},
-					Sprite {
-						color: [0.0, 0.0, 0.0, 0.0],
-						sprite: [[46.0, 2.0], [45.0, 2.0], [47.0, 2.0]]
-							[rand::thread_rng().gen_range(0, 3)],
-					},
-					Life { health: 10.0 },
-					Physics {
-						acceleration: glam::Vec3::new(0.0, 0.0, 0.0),
-						deceleration: 0.0,
-					},
-					ContactDamage {
-						damage: 5.0,
-						once: true,
-					},
-				),
-			);
-			spawner.last = timer.lifetime()
-				+ rand::thread_rng().gen_range(spawner.spawnrate / 2.0, spawner.spawnrate * 2.0);
+	spatial.clear();
+	let mut missing = Vec::new();
+	for (id, (transform, collider)) in (&transforms, &colliders).iter().with_id() {
+		let shape = collider.shape.to_world(collider.offset, transform);
+		let (center, radius) = swept_bounds(&shape, sweep(&physics, id, clock.delta()));
+		spatial.insert(id, center, radius);
+		if let Ok(existing) = (&mut shapes).get(id) {
+			*existing = shape;
+		} else {
+			missing.push((id, shape));
 		}
-	});
+	}
+	for (id, shape) in missing {
+		entities.add_component(&mut shapes, shape, id);
+	}
+	// shapes of entities that lost their collider or transform would keep colliding
+	let stale = (&shapes)
+		.iter()
+		.with_id()
+		.filter(|(id, _)| (&colliders).get(*id).is_err() || (&transforms).get(*id).is_err())
+		.map(|(id, _)| id)
+		.collect::<Vec<_>>();
+	for id in stale {
+		Delete::<(WorldShape,)>::delete((&mut shapes,), id);
+	}
+}
+
+// the movement of a continuous collision body during the coming integration
+fn sweep(physics: &View<Physics>, id: EntityId, delta: f32) -> glam::Vec2 {
+	match physics.get(id) {
+		Ok(physics) if physics.ccd => {
+			glam::vec2(physics.velocity.x(), physics.velocity.y()) * delta
+		},
+		_ => glam::Vec2::zero(),
+	}
+}
+
+fn swept_bounds(shape: &WorldShape, sweep: glam::Vec2) -> (glam::Vec2, f32) {
+	let (center, radius) = shape.bounds();
+	(center + sweep * 0.5, radius + sweep.length() * 0.5)
+}
+
+// finds the overlapping pairs where at least one of the entities collides with the other,
+// continuous collision bodies are tested along their movement of the tick
+#[flame]
+pub fn collision(
+	shapes: View<WorldShape>, colliders: View<Collider>, physics: View<Physics>,
+	spatial: UniqueView<SpatialHash<EntityId>>, clock: UniqueView<Clock>,
+	mut contacts: UniqueViewMut<Contacts>, mut enters: UniqueViewMut<Events<CollisionEnter>>,
+	mut stays: UniqueViewMut<Events<CollisionStay>>,
+	mut exits: UniqueViewMut<Events<CollisionExit>>,
+) {
+	let mut pairs = Vec::new();
+	for (id, (shape, collider)) in (&shapes, &colliders).iter().with_id() {
+		let motion = sweep(&physics, id, clock.delta());
+		let (center, radius) = swept_bounds(shape, motion);
+		for t_id in spatial.query_radius(center, radius) {
+			// every pair is tested once, from the entity with the lower index
+			if id.index() >= t_id.index() {
+				continue;
+			}
+			let (t_shape, t_collider) = match ((&shapes).get(t_id), (&colliders).get(t_id)) {
+				(Ok(t_shape), Ok(t_collider)) => (t_shape, t_collider),
+				_ => continue,
+			};
+			if !collider.interacts(t_collider) && !t_collider.interacts(collider) {
+				continue;
+			}
+			let t_motion = sweep(&physics, t_id, clock.delta());
+			if let Some(contact) = intersect_swept(shape, motion, t_shape, t_motion) {
+				pairs.push((id, t_id, contact));
+			}
+		}
+	}
+	contacts.update(pairs);
+
+	for &(a, b, contact) in contacts.iter() {
+		if contacts.was_touching(a, b) {
+			stays.send(CollisionStay {
+				a,
+				b,
+				normal: contact.normal,
+				depth: contact.depth,
+			});
+		} else {
+			enters.send(CollisionEnter {
+				a,
+				b,
+				normal: contact.normal,
+				depth: contact.depth,
+			});
+		}
+	}
+	for (a, b) in contacts.ended() {
+		exits.send(CollisionExit { a, b });
+	}
+}
+
+// separates solid colliders weighted by their mass, entities without physics don't move
+#[flame]
+pub fn collisionresponse(
+	colliders: View<Collider>, mut transforms: ViewMut<Transform>, mut physics: ViewMut<Physics>,
+	contacts: UniqueView<Contacts>,
+) {
+	for &(a, b, contact) in contacts.iter() {
+		let (collider, t_collider) = match ((&colliders).get(a), (&colliders).get(b)) {
+			(Ok(collider), Ok(t_collider)) if collider.solid && t_collider.solid => {
+				(collider, t_collider)
+			},
+			_ => continue,
+		};
+		let inverse_a = (&physics).get(a).map(Physics::inverse_mass).unwrap_or(0.0);
+		let inverse_b = (&physics).get(b).map(Physics::inverse_mass).unwrap_or(0.0);
+		let inverse = inverse_a + inverse_b;
+		if inverse <= 0.0 {
+			continue;
+		}
+		let normal = glam::Vec3::new(contact.normal.x(), contact.normal.y(), 0.0);
+
+		let correction = normal * contact.depth / inverse;
+		if let Ok(transform) = (&mut transforms).get(a) {
+			transform.position -= correction * inverse_a;
+		}
+		if let Ok(transform) = (&mut transforms).get(b) {
+			transform.position += correction * inverse_b;
+		}
+
+		// bounce when the entities move toward each other
+		let velocity_a = (&physics).get(a).map(|p| p.velocity).unwrap_or_default();
+		let velocity_b = (&physics).get(b).map(|p| p.velocity).unwrap_or_default();
+		let approach = (velocity_b - velocity_a).dot(normal);
+		if approach < 0.0 {
+			let restitution = collider.restitution.max(t_collider.restitution);
+			let impulse = normal * -(1.0 + restitution) * approach / inverse;
+			if let Ok(physics) = (&mut physics).get(a) {
+				physics.velocity -= impulse * inverse_a;
+			}
+			if let Ok(physics) = (&mut physics).get(b) {
+				physics.velocity += impulse * inverse_b;
+			}
+		}
+	}
+}
+
+// pushes the other entity away when a solid contact starts, independent of the physics backend
+#[flame]
+pub fn knockback(
+	colliders: View<Collider>, mut physics: ViewMut<Physics>,
+	enters: UniqueView<Events<CollisionEnter>>,
+) {
+	for enter in enters.iter() {
+		let (collider, t_collider) = match ((&colliders).get(enter.a), (&colliders).get(enter.b)) {
+			(Ok(collider), Ok(t_collider)) if collider.solid && t_collider.solid => {
+				(collider, t_collider)
+			},
+			_ => continue,
+		};
+		let normal = glam::Vec3::new(enter.normal.x(), enter.normal.y(), 0.0);
+		if let Ok(physics) = (&mut physics).get(enter.b) {
+			physics.apply_impulse(normal * collider.knockback);
+		}
+		if let Ok(physics) = (&mut physics).get(enter.a) {
+			physics.apply_impulse(-normal * t_collider.knockback);
+		}
+	}
 }
 
 #[flame]
 pub fn contactdamage(
-	transforms: View<Transform>, contactdamages: View<ContactDamage>, enemies: View<Enemy>,
-	mut lifes: ViewMut<Life>,
+	contactdamages: View<ContactDamage>, colliders: View<Collider>, factions: View<Faction>,
+	owners: View<Owner>, players: View<Player>, mut lifes: ViewMut<Life>,
+	contacts: UniqueView<Contacts>, mut damages: UniqueViewMut<Events<DamageDealt>>,
+	mut hits: UniqueViewMut<Events<PlayerHit>>,
 ) {
 	let mut deads = Vec::new();
-	(&transforms, &contactdamages)
-		.iter()
-		.with_id()
-		.for_each(|(id, (transform, contactdamage))| {
-			(&transforms, &mut lifes)
-				.iter()
-				.with_id()
-				.for_each(|(t_id, (t_transform, t_life))| {
-					if id != t_id && ((&enemies).get(id).is_err() || (&enemies).get(t_id).is_err())
-					{
-						let dx = transform.position.x() - t_transform.position.x();
-						let dy = transform.position.y() - t_transform.position.y();
-						let di = f32::sqrt(dx * dx + dy * dy);
-						if di < transform.scale[0] + t_transform.scale[0] {
-							t_life.health -= contactdamage.damage;
-							if contactdamage.once {
-								deads.push(id);
-							}
-						}
-					}
+	for &(a, b, _) in contacts.iter() {
+		for &(id, t_id) in &[(a, b), (b, a)] {
+			let contactdamage = match (&contactdamages).get(id) {
+				Ok(contactdamage) => contactdamage,
+				Err(_) => continue,
+			};
+			match ((&colliders).get(id), (&colliders).get(t_id)) {
+				(Ok(collider), Ok(t_collider)) if collider.interacts(t_collider) => (),
+				_ => continue,
+			}
+			// never damage the owner, and the own faction only with friendly fire
+			if let Ok(owner) = (&owners).get(id) {
+				if owner.entity == t_id {
+					continue;
+				}
+			}
+			if let (Ok(faction), Ok(t_faction)) = ((&factions).get(id), (&factions).get(t_id)) {
+				if faction == t_faction && !contactdamage.friendly_fire {
+					continue;
+				}
+			}
+			if let Ok(t_life) = (&mut lifes).get(t_id) {
+				t_life.health -= contactdamage.damage;
+				damages.send(DamageDealt {
+					source: id,
+					target: t_id,
+					amount: contactdamage.damage,
 				});
-		});
+				if (&players).get(t_id).is_ok() {
+					hits.send(PlayerHit {
+						player: t_id,
+						source: id,
+						amount: contactdamage.damage,
+					});
+				}
+				if contactdamage.once {
+					deads.push(id);
+				}
+			}
+		}
+	}
 	for dead in deads {
 		(&mut lifes)
 			.get(dead)
@@ -214,21 +426,18 @@ pub fn contactdamage(
 #[flame]
 pub fn enemyai(
 	transforms: View<Transform>, mut physics: ViewMut<Physics>, enemies: View<Enemy>,
-	players: View<Player>, timer: UniqueView<Timer>,
+	players: View<Player>,
 ) {
 	for (transform, physic, _) in (&transforms, &mut physics, &enemies).iter() {
 		for (_, p_transform) in (&players, &transforms).iter() {
-			let accel = physic.acceleration
-				+ glam::Vec3::new(
+			let mass = physic.mass;
+			physic.apply_force(
+				glam::Vec3::new(
 					p_transform.position.x() - transform.position.x(),
 					p_transform.position.y() - transform.position.y(),
 					0.0,
 				) * ACCELERATION / 10.0
-					* timer.delta();
-			physic.acceleration = glam::Vec3::new(
-				accel.x().clamp(-MAXSPEED, MAXSPEED),
-				accel.y().clamp(-MAXSPEED, MAXSPEED),
-				0.0,
+					* mass,
 			);
 		}
 	}
@@ -236,18 +445,17 @@ pub fn enemyai(
 
 #[flame]
 pub fn selfdamage(
-	mut lifes: ViewMut<Life>, mut selfdamages: ViewMut<SelfDamage>, timer: UniqueView<Timer>,
+	mut lifes: ViewMut<Life>, mut selfdamages: ViewMut<SelfDamage>, clock: UniqueView<Clock>,
 ) {
 	(&mut lifes, &mut selfdamages)
 		.iter()
 		.for_each(|(life, selfdamage)| {
-			life.health -= selfdamage.damage * timer.delta();
+			life.health -= selfdamage.damage * clock.delta();
 		});
 }
 
 #[flame]
-pub fn death(mut entities: AllStoragesViewMut) -> u32 {
-	let mut score_mod = 0;
+pub fn death(mut entities: AllStoragesViewMut) {
 	let mut delete_entities = Vec::new();
 	entities.run(|lifes: View<Life>| {
 		for (id, life) in (&lifes).iter().with_id() {
@@ -257,60 +465,178 @@ pub fn death(mut entities: AllStoragesViewMut) -> u32 {
 		}
 	});
 	for id in delete_entities {
-		entities.run(|enemies: View<Enemy>| {
-			if (&enemies).get(id).is_ok() {
-				score_mod += 1;
-			}
-		});
+		entities.run(
+			|transforms: View<Transform>,
+			 enemies: View<Enemy>,
+			 players: View<Player>,
+			 mut died: UniqueViewMut<Events<EntityDied>>| {
+				died.send(EntityDied {
+					entity: id,
+					position: (&transforms).get(id).map(|t| t.position).ok(),
+					enemy: (&enemies).get(id).is_ok(),
+					player: (&players).get(id).is_ok(),
+				});
+			},
+		);
 		entities.delete(id);
 	}
-	score_mod
+}
+
+#[flame]
+pub fn score(died: UniqueView<Events<EntityDied>>, mut session: UniqueViewMut<Session>) {
+	session.score += died.iter().filter(|died| died.enemy).count() as i32;
+}
+
+// runs the scheduled tasks that are due, outside of any borrow so they can use all storages
+#[flame]
+pub fn schedule(all_storages: AllStoragesViewMut) {
+	let now = all_storages.run(|clock: UniqueView<Clock>| clock.time());
+	let mut due =
+		all_storages.run(|mut scheduler: UniqueViewMut<Scheduler>| scheduler.take_due(now));
+	for task in &mut due {
+		task.run(&all_storages);
+	}
+	all_storages.run(|mut scheduler: UniqueViewMut<Scheduler>| scheduler.requeue(due));
+}
+
+#[flame]
+pub fn hitstop(hits: UniqueView<Events<PlayerHit>>, mut clock: UniqueViewMut<Clock>) {
+	if !hits.is_empty() {
+		clock.hit_stop(HIT_STOP);
+	}
+}
+
+#[flame]
+pub fn events(
+	mut damages: UniqueViewMut<Events<DamageDealt>>, mut died: UniqueViewMut<Events<EntityDied>>,
+	mut fired: UniqueViewMut<Events<ProjectileFired>>, mut hits: UniqueViewMut<Events<PlayerHit>>,
+	mut enters: UniqueViewMut<Events<CollisionEnter>>,
+	mut stays: UniqueViewMut<Events<CollisionStay>>,
+	mut exits: UniqueViewMut<Events<CollisionExit>>,
+) {
+	damages.begin_tick();
+	died.begin_tick();
+	fired.begin_tick();
+	hits.begin_tick();
+	enters.begin_tick();
+	stays.begin_tick();
+	exits.begin_tick();
+}
+
+// runs once per frame before the ticks, after the previous frame was updated and rendered
+#[flame]
+pub fn clear_events(
+	mut damages: UniqueViewMut<Events<DamageDealt>>, mut died: UniqueViewMut<Events<EntityDied>>,
+	mut fired: UniqueViewMut<Events<ProjectileFired>>, mut hits: UniqueViewMut<Events<PlayerHit>>,
+	mut enters: UniqueViewMut<Events<CollisionEnter>>,
+	mut stays: UniqueViewMut<Events<CollisionStay>>,
+	mut exits: UniqueViewMut<Events<CollisionExit>>,
+) {
+	damages.clear();
+	died.clear();
+	fired.clear();
+	hits.clear();
+	enters.clear();
+	stays.clear();
+	exits.clear();
 }
 
 #[flame]
 pub fn camera(
-	transforms: View<Transform>, mut cameras: ViewMut<Camera>, camerafollow: View<CameraFollow>,
-	timer: UniqueView<Timer>,
+	transforms: View<Transform>, previous: View<PreviousTransform>, mut cameras: ViewMut<Camera>,
+	camerafollow: View<CameraFollow>, clock: UniqueView<Clock>,
 ) {
 	(&mut cameras, &camerafollow)
 		.iter()
 		.for_each(|(camera, camerafollow)| {
 			if let Ok(transform) = (&transforms).get(camerafollow.entity) {
-				let pos = &transform.position;
-				let target = (pos.x(), pos.y(), 100.0).into();
-				let eye = (pos.x(), pos.y(), 0.0).into();
-				camera.target = target;
-				camera.eye = camera
-					.eye
-					.lerp(eye, (camera.eye - eye).length() / 5.0 * timer.delta());
+				let pos = (&previous)
+					.get(camerafollow.entity)
+					.map(|previous| previous.position.lerp(transform.position, clock.alpha()))
+					.unwrap_or(transform.position);
+				let pose = camera.following(pos);
+				camera.target = pose.target;
+				camera.eye = camera.eye.lerp(
+					pose.eye,
+					(camera.eye - pose.eye).length() / 5.0 * clock.frame_delta(),
+				);
 			}
 		});
 }
 
+#[flame]
+pub fn snapshot(
+	entities: EntitiesView, transforms: View<Transform>, mut previous: ViewMut<PreviousTransform>,
+) {
+	let mut missing = Vec::new();
+	for (id, transform) in (&transforms).iter().with_id() {
+		if let Ok(previous) = (&mut previous).get(id) {
+			previous.position = transform.position;
+			previous.rotation = transform.rotation;
+		} else {
+			missing.push((id, PreviousTransform {
+				position: transform.position,
+				rotation: transform.rotation,
+			}));
+		}
+	}
+	for (id, component) in missing {
+		entities.add_component(&mut previous, component, id);
+	}
+}
+
 #[flame]
 pub fn physics(
-	mut transforms: ViewMut<Transform>, mut physics: ViewMut<Physics>, timer: UniqueView<Timer>,
+	mut transforms: ViewMut<Transform>, mut physics: ViewMut<Physics>, aims: View<Aim>,
+	clock: UniqueView<Clock>,
 ) {
-	for (transform, physics) in (&mut transforms, &mut physics).iter() {
-		transform.position += physics.acceleration * timer.delta();
-		physics.acceleration -= physics.acceleration * physics.deceleration * timer.delta();
+	let delta = clock.delta();
+	for (id, (transform, physics)) in (&mut transforms, &mut physics).iter().with_id() {
+		// semi-implicit euler, the position moves with the already updated velocity
+		physics.velocity += physics.acceleration * delta;
+		physics.velocity -= physics.velocity * (physics.drag * delta).min(1.0);
+		if let Some(max_speed) = physics.max_speed {
+			if physics.velocity.length() > max_speed {
+				physics.velocity = physics.velocity.normalize() * max_speed;
+			}
+		}
+		transform.position += physics.velocity * delta;
+		physics.acceleration = glam::Vec3::zero();
 
-		let a = if physics.acceleration.length() > 0.0 {
-			glam::vec2(physics.acceleration.x(), physics.acceleration.y()).normalize()
-		} else {
-			glam::vec2(physics.acceleration.x(), physics.acceleration.y())
-		};
+		if physics.angular_velocity != 0.0 {
+			*transform.rotation.x_mut() += physics.angular_velocity * delta;
+		} else if (&aims).get(id).map_or(true, |aim| aim.direction.is_none()) {
+			face(transform, physics.velocity);
+		}
+	}
+}
+
+// spinning and aiming entities don't face their movement, so this is only used for the others
+pub fn face(transform: &mut Transform, velocity: glam::Vec3) {
+	if velocity.length() > 0.0 {
+		let a = glam::vec2(velocity.x(), velocity.y()).normalize();
 		transform.rotation = glam::Vec3::new(-f32::atan2(a.x(), a.y()), 0.0, 0.0);
 	}
 }
 
 
+fn lerp_angle(from: f32, to: f32, alpha: f32) -> f32 {
+	use std::f32::consts::{PI, TAU};
+	from + ((to - from + PI).rem_euclid(TAU) - PI) * alpha
+}
+
 #[flame]
 pub fn render(
-	positions: View<Transform>, sprites: View<Sprite>, cameras: View<Camera>,
+	positions: View<Transform>, previous: View<PreviousTransform>, sprites: View<Sprite>,
+	shapes: View<WorldShape>, cameras: View<Camera>, clock: UniqueView<Clock>,
 	mut renderer: UniqueViewMut<Renderer>,
 ) {
 	const SPRITE_SIZE: f32 = 16.0;
+	const DEBUG_SPRITE: [f32; 2] = [1.0, 1.0];
+	const DEBUG_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 0.5];
+	const DEBUG_SIZE: f32 = 0.03;
+	const DEBUG_SPACING: f32 = 0.1;
+	const DEBUG_DEPTH: f32 = 10.0;
 
 	if renderer.swapchain.is_none() || renderer.camera.is_none() {
 		return;
@@ -363,23 +689,72 @@ pub fn render(
 	renderer.queue.submit(Some(encoder.finish()));
 
 	let camera = CameraArgs {
-		projection: *{
-			let view = glam::Mat4::look_at_lh(camera.eye, camera.target, camera.up);
-			let proj = glam::Mat4::perspective_lh(
-				camera.fovy.to_radians(),
-				renderer.width as f32 / renderer.height as f32,
-				camera.znear,
-				camera.zfar,
-			);
-			proj * view
-		}
-		.as_ref(),
+		projection: *camera
+			.view_projection(renderer.width as f32 / renderer.height as f32)
+			.as_ref(),
 	};
 	renderer
 		.queue
 		.write_buffer(&renderer.camera_args, 0, &[camera].as_bytes());
 
-	let mut iter = (&positions, &sprites).iter();
+	let alpha = clock.alpha();
+	let mut sprite_args = Vec::new();
+	for (id, (transform, sprite)) in (&positions, &sprites).iter().with_id() {
+		let (position, rotation) = match (&previous).get(id) {
+			Ok(previous) => (
+				previous.position.lerp(transform.position, alpha),
+				glam::Vec3::new(
+					lerp_angle(previous.rotation.x(), transform.rotation.x(), alpha),
+					lerp_angle(previous.rotation.y(), transform.rotation.y(), alpha),
+					lerp_angle(previous.rotation.z(), transform.rotation.z(), alpha),
+				),
+			),
+			Err(_) => (transform.position, transform.rotation),
+		};
+		sprite_args.push(SpriteArgs {
+			position: position.into(),
+			_1: 0.0,
+			size: transform.scale,
+			_2: [0.0, 0.0],
+			color: sprite.color,
+			rotation: rotation.into(),
+			_3: 0.0,
+			texturecoords: [
+				sprite.sprite[0] * SPRITE_SIZE,
+				sprite.sprite[1] * SPRITE_SIZE,
+			],
+			texturesize: [SPRITE_SIZE, SPRITE_SIZE],
+		});
+	}
+	// collider outlines are dotted with small sprites at the latest simulated position
+	if renderer.debug_colliders {
+		for shape in (&shapes).iter() {
+			let outline = shape.outline();
+			for (i, from) in outline.iter().enumerate() {
+				let to = outline[(i + 1) % outline.len()];
+				let steps = ((to - *from).length() / DEBUG_SPACING).ceil().max(1.0) as usize;
+				for step in 0..steps {
+					let point = from.lerp(to, step as f32 / steps as f32);
+					sprite_args.push(SpriteArgs {
+						position: [point.x(), point.y(), DEBUG_DEPTH],
+						_1: 0.0,
+						size: [DEBUG_SIZE, DEBUG_SIZE],
+						_2: [0.0, 0.0],
+						color: DEBUG_COLOR,
+						rotation: [0.0, 0.0, 0.0],
+						_3: 0.0,
+						texturecoords: [
+							DEBUG_SPRITE[0] * SPRITE_SIZE,
+							DEBUG_SPRITE[1] * SPRITE_SIZE,
+						],
+						texturesize: [SPRITE_SIZE, SPRITE_SIZE],
+					});
+				}
+			}
+		}
+	}
+
+	let mut iter = sprite_args.iter();
 	let mut repeat = true;
 	while repeat {
 		repeat = false;
@@ -403,25 +778,11 @@ pub fn render(
 			render_pass.set_pipeline(&renderer.sprite_pipeline);
 
 			let mut offset = 0;
-			while let Some((transform, sprite)) = iter.next() {
-				let args = SpriteArgs {
-					position: transform.position.into(),
-					_1: 0.0,
-					size: transform.scale,
-					_2: [0.0, 0.0],
-					color: sprite.color,
-					rotation: transform.rotation.into(),
-					_3: 0.0,
-					texturecoords: [
-						sprite.sprite[0] * SPRITE_SIZE,
-						sprite.sprite[1] * SPRITE_SIZE,
-					],
-					texturesize: [SPRITE_SIZE, SPRITE_SIZE],
-				};
+			while let Some(args) = iter.next() {
 				renderer.queue.write_buffer(
 					&renderer.sprite_args,
 					offset as wgpu::BufferAddress,
-					&[args].as_bytes(),
+					&[*args].as_bytes(),
 				);
 
 				render_pass.set_bind_group(0, &renderer.sprite_bind_group, &[
@@ -444,15 +805,18 @@ pub fn render(
 
 #[flame]
 pub fn status(
-	players: View<Player>, enemies: View<Enemy>, timer: UniqueView<Timer>,
+	players: View<Player>, enemies: View<Enemy>, paused: View<Paused>, clock: UniqueView<Clock>,
 	session: UniqueView<Session>,
 ) -> String {
 	let status = format!(
 		"{} ({:.1} fps / {:.3} ms)",
 		env!("CARGO_PKG_NAME"),
-		timer.frames_per_second_smooth(),
-		timer.frame_time_smooth(),
+		clock.frames_per_second_smooth(),
+		clock.frame_time_smooth(),
 	);
+	if !paused.is_empty() {
+		return format!("{} Score: {} - PAUSED", status, session.score);
+	}
 	if !(&players).is_empty() {
 		return format!(
 			"{} Score: {} ({} Enemies alive)",
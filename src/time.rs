@@ -1,48 +1,142 @@
-use chrono::*;
-use std::collections::*;
+use std::{
+	collections::*,
+	time::{Duration, Instant},
+};
 
-pub struct Timer {
+
+// real time is measured with a monotonic clock, game time only advances with simulated ticks
+pub struct Clock {
 	frame_time: f32,
 	frame_coll: VecDeque<f32>,
 	frame_smooth_count: usize,
-	now: NaiveTime,
-	start: NaiveTime,
-
-	lifetime: f32,
+	last: Instant,
+	real_time: f64,
+
+	tick: f32,
+	accumulator: f32,
+	ticks: u64,
+	stepped: bool,
+
+	time: f64,
+	unscaled_time: f64,
+	time_scale: f32,
+	paused: bool,
+	hit_stop: f32,
 }
-impl Timer {
-	pub fn new(frame_smooth_count: usize) -> Self {
+impl Clock {
+	pub fn new(frame_smooth_count: usize, tick_rate: f32) -> Self {
 		Self {
 			frame_time: 0f32,
 			frame_coll: std::collections::VecDeque::new(),
 			frame_smooth_count,
-			now: chrono::Utc::now().naive_utc().time(),
-			start: chrono::Utc::now().naive_utc().time(),
-			lifetime: 0.0,
+			last: Instant::now(),
+			real_time: 0.0,
+			tick: 1.0 / tick_rate,
+			accumulator: 0.0,
+			ticks: 0,
+			stepped: true,
+			time: 0.0,
+			unscaled_time: 0.0,
+			time_scale: 1.0,
+			paused: false,
+			hit_stop: 0.0,
 		}
 	}
 
 	pub fn update(&mut self) {
-		self.now = chrono::Utc::now().naive_utc().time();
-		self.frame_time = (self
-			.now
-			.signed_duration_since(self.start)
-			.num_microseconds()
-			.unwrap_or(0) as f64
-			/ 1000.0) as f32;
-		self.start = self.now;
+		let now = Instant::now();
+		let frame_time = now.duration_since(self.last).as_secs_f32() * 1000.0;
+		self.last = now;
+		self.advance(frame_time);
+	}
+
+	pub fn advance(&mut self, frame_time: f32) {
+		self.frame_time = frame_time;
+		self.real_time += f64::from(frame_time) / 1000.0;
 		self.frame_coll.push_back(self.frame_time);
 		if self.frame_coll.len() >= self.frame_smooth_count {
 			self.frame_coll.pop_front();
 		}
-		self.lifetime += self.frame_time / 1000f32;
 	}
 
+	// adds the last frame time to the time available for fixed ticks
+	pub fn accumulate(&mut self) { self.accumulator += self.frame_time / 1000f32; }
+
+	// takes one fixed tick from the accumulated time if available
+	pub fn consume_tick(&mut self) -> bool {
+		if self.accumulator < self.tick {
+			return false;
+		}
+		self.accumulator -= self.tick;
+		self.ticks += 1;
+		true
+	}
+
+	// drops accumulated time that couldn't be caught up with, keeping the partial tick
+	pub fn discard(&mut self) { self.accumulator %= self.tick; }
+
+	// advances the game time by one tick, returns whether the simulation should step
+	pub fn step(&mut self, simulating: bool) -> bool {
+		self.stepped = simulating && !self.paused && self.hit_stop <= 0.0;
+		if simulating && !self.paused && self.hit_stop > 0.0 {
+			self.hit_stop -= self.tick;
+		}
+		if self.stepped {
+			self.unscaled_time += f64::from(self.tick);
+			self.time += f64::from(self.delta());
+		}
+		self.stepped
+	}
+
+	pub fn pause(&mut self) { self.paused = true; }
+
+	pub fn resume(&mut self) { self.paused = false; }
+
+	pub fn is_paused(&self) -> bool { self.paused }
+
+	// freezes the simulation for the given unscaled duration, overlapping hit-stops don't add up
+	pub fn hit_stop(&mut self, duration: f32) { self.hit_stop = self.hit_stop.max(duration); }
+
+	pub fn is_frozen(&self) -> bool { self.paused || self.hit_stop > 0.0 }
+
+	pub fn set_time_scale(&mut self, time_scale: f32) { self.time_scale = time_scale.max(0.0); }
+
+	pub fn time_scale(&self) -> f32 { self.time_scale }
+
 	pub fn frame_time(&self) -> f32 { self.frame_time }
 
-	pub fn delta(&self) -> f32 { self.frame_time / 1000f32 }
+	// real seconds since the last frame
+	pub fn frame_delta(&self) -> f32 { self.frame_time / 1000f32 }
 
-	pub fn lifetime(&self) -> f32 { self.lifetime }
+	// scaled game seconds per tick
+	pub fn delta(&self) -> f32 { self.tick * self.time_scale }
+
+	pub fn unscaled_delta(&self) -> f32 { self.tick }
+
+	pub fn ticks(&self) -> u64 { self.ticks }
+
+	pub fn set_ticks(&mut self, ticks: u64) { self.ticks = ticks; }
+
+	// stays at the latest state while the simulation doesn't step
+	pub fn alpha(&self) -> f32 {
+		if self.stepped {
+			(self.accumulator / self.tick).min(1.0)
+		} else {
+			1.0
+		}
+	}
+
+	// scaled game time in seconds
+	pub fn time(&self) -> f32 { self.time as f32 }
+
+	pub fn unscaled_time(&self) -> f32 { self.unscaled_time as f32 }
+
+	pub fn real_time(&self) -> f32 { self.real_time as f32 }
+
+	pub fn set_time(&mut self, time: f32, unscaled_time: f32) {
+		self.time = f64::from(time);
+		self.unscaled_time = f64::from(unscaled_time);
+	}
 
 	pub fn frame_time_smooth(&self) -> f32 {
 		self.frame_coll.iter().sum::<f32>() / self.frame_coll.len() as f32
@@ -62,8 +156,7 @@ pub struct FrameAccumTimer {
 	callback_interval: f32,
 	frame_time_accum: f32,
 
-	now: NaiveTime,
-	start: NaiveTime,
+	last: Instant,
 
 	lifetime: f32,
 }
@@ -76,22 +169,16 @@ impl FrameAccumTimer {
 			frame_coll: std::collections::VecDeque::new(),
 			frame_time_accum: 0f32,
 			frame_smooth_count,
-			now: chrono::Utc::now().naive_utc().time(),
-			start: chrono::Utc::now().naive_utc().time(),
+			last: Instant::now(),
 			callback_interval,
 			lifetime: 0.0,
 		}
 	}
 
 	pub fn update(&mut self) {
-		self.now = chrono::Utc::now().naive_utc().time();
-		self.frame_time = (self
-			.now
-			.signed_duration_since(self.start)
-			.num_microseconds()
-			.unwrap_or(0) as f64
-			/ 1000.0) as f32;
-		self.start = self.now;
+		let now = Instant::now();
+		self.frame_time = now.duration_since(self.last).as_secs_f32() * 1000.0;
+		self.last = now;
 		self.frame_coll.push_back(self.frame_time);
 		self.frame_time_accum += self.frame_time;
 		self.lifetime += self.frame_time / 1000f32;
@@ -121,3 +208,39 @@ impl FrameAccumTimer {
 
 	pub fn frames_per_second_smooth(&self) -> f32 { 1.0 / (self.frame_time_smooth / 1000f32) }
 }
+
+
+// paces frames to a maximum rate so the event loop can sleep in between instead of spinning
+pub struct FrameLimiter {
+	interval: Option<Duration>,
+	next: Instant,
+}
+
+impl FrameLimiter {
+	pub fn new(cap: Option<f32>) -> Self {
+		let mut limiter = Self {
+			interval: None,
+			next: Instant::now(),
+		};
+		limiter.set_cap(cap);
+		limiter
+	}
+
+	pub fn set_cap(&mut self, cap: Option<f32>) {
+		self.interval = cap
+			.filter(|cap| *cap > 0.0)
+			.map(|cap| Duration::from_secs_f32(1.0 / cap));
+	}
+
+	pub fn is_ready(&self, now: Instant) -> bool { self.interval.is_none() || now >= self.next }
+
+	// schedules the next frame, without trying to catch up on frames that were late
+	pub fn finish(&mut self, now: Instant) {
+		if let Some(interval) = self.interval {
+			self.next = (self.next + interval).max(now);
+		}
+	}
+
+	// when the next frame is due, none when uncapped
+	pub fn next(&self) -> Option<Instant> { self.interval.map(|_| self.next) }
+}
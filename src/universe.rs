@@ -1,68 +1,175 @@
 use anyhow::Error;
 use flamer::flame;
-use log::info;
-use shipyard::{system, AllStoragesViewMut, Get, UniqueView, UniqueViewMut, View, ViewMut, World};
-use std::{cell::RefCell, mem::size_of};
+use log::{error, info, warn};
+use shipyard::{
+	AllStoragesViewMut, EntityId, Get, UniqueView, UniqueViewMut, View, ViewMut, World,
+};
+use std::{cell::RefCell, mem::size_of, path::Path};
 use winit::{
-	event::{ElementState, Event, VirtualKeyCode},
+	event::{ElementState, Event, WindowEvent},
 	window::Window,
 };
 
+#[cfg(feature = "physics-rapier")]
+use crate::rapier::RapierWorld;
 use crate::{
+	actions::{Action, Actions, Bindings},
+	collision::Contacts,
 	components::Camera,
+	events::{
+		CollisionEnter, CollisionExit, CollisionStay, DamageDealt, EntityDied, Events, PlayerHit,
+		ProjectileFired,
+	},
+	gamepad::{GamepadSource, Gamepads, GilrsSource},
 	graphics::{get_buffer_size, BackgroundArgs, CameraArgs, Renderer, SpriteArgs},
 	input::Input,
+	prefab::{self, Prefabs},
+	replay::{Recorder, Replay},
 	resources::{self, get_shader},
+	rng::Rng,
+	save,
+	scene::{Scene, SceneComponent, SceneInstance},
+	scheduler::Scheduler,
 	session::Session,
-	states::{EmptyState, State},
+	spatial::SpatialHash,
+	states::State,
 	systems,
-	time::Timer,
+	time::Clock,
 	util::create_swap_chain_descriptor,
+	workloads::{self, Workloads},
 };
 
 
+const TICK_RATE: f32 = 60.0;
+const MAX_TICKS_PER_FRAME: u32 = 5;
+const BROADPHASE_CELL_SIZE: f32 = 2.0;
+
+
+pub type StateBuilder = Box<dyn FnOnce(&Universe) -> Box<RefCell<dyn State>>>;
+
+pub enum Transition {
+	Push(StateBuilder),
+	Pop,
+	Replace(StateBuilder),
+	Switch(StateBuilder),
+}
+
+impl Transition {
+	pub fn push<T: State + Sized + 'static>() -> Self { Transition::Push(Self::builder::<T>()) }
+
+	pub fn replace<T: State + Sized + 'static>() -> Self {
+		Transition::Replace(Self::builder::<T>())
+	}
+
+	pub fn switch<T: State + Sized + 'static>() -> Self { Transition::Switch(Self::builder::<T>()) }
+
+	fn builder<T: State + Sized + 'static>() -> StateBuilder {
+		Box::new(|universe| Box::new(RefCell::new(T::new(universe))))
+	}
+}
+
+
 pub struct Universe {
 	pub world: World,
-	pub state: Box<RefCell<dyn State>>,
+	pub states: Vec<Box<RefCell<dyn State>>>,
+	transitions: RefCell<Vec<Transition>>,
+	recorder: Option<Recorder>,
+	replay: Option<Replay>,
+	gamepads: Gamepads,
+	present_mode: wgpu::PresentMode,
+	pub headless: bool,
 }
 
 
 impl Universe {
-	pub fn new(device: wgpu::Device, queue: wgpu::Queue) -> Result<Self, Error> {
+	pub fn new(device: wgpu::Device, queue: wgpu::Queue, seed: u64) -> Result<Self, Error> {
+		let mut universe = Self::headless(seed)?;
+		universe.world.add_unique(Renderer::new(device, queue)?);
+		match GilrsSource::new() {
+			Ok(source) => universe.set_gamepad_source(Box::new(source)),
+			Err(error) => warn!("gamepads are unavailable: {}", error),
+		}
+		universe.headless = false;
+		Ok(universe)
+	}
+
+	pub fn headless(seed: u64) -> Result<Self, Error> {
 		let universe = Self {
 			world: World::new(),
-			state: Box::from(RefCell::new(EmptyState {})),
+			states: Vec::new(),
+			transitions: RefCell::new(Vec::new()),
+			recorder: None,
+			replay: None,
+			gamepads: Gamepads::new(),
+			present_mode: wgpu::PresentMode::Mailbox,
+			headless: true,
 		};
 
-		universe.world.add_unique(Renderer::new(device, queue)?);
-		universe.world.add_unique(Timer::new(20));
+		universe.world.add_unique(Clock::new(20, TICK_RATE));
 		universe.world.add_unique(Session::new());
 		universe.world.add_unique(Input::new());
+		universe.world.add_unique(Actions::new(1.0 / TICK_RATE));
+		universe.world.add_unique(Bindings::load()?);
+		universe.world.add_unique(Rng::new(seed));
+		universe.world.add_unique(Prefabs::load()?);
+		universe.world.add_unique(Scheduler::new());
+		universe
+			.world
+			.add_unique(SpatialHash::<EntityId>::new(BROADPHASE_CELL_SIZE));
+		universe.world.add_unique(Contacts::new());
+		#[cfg(feature = "physics-rapier")]
+		universe.world.add_unique(RapierWorld::new());
+		universe.world.add_unique(Events::<DamageDealt>::new());
+		universe.world.add_unique(Events::<EntityDied>::new());
+		universe.world.add_unique(Events::<ProjectileFired>::new());
+		universe.world.add_unique(Events::<PlayerHit>::new());
+		universe.world.add_unique(Events::<CollisionEnter>::new());
+		universe.world.add_unique(Events::<CollisionStay>::new());
+		universe.world.add_unique(Events::<CollisionExit>::new());
 
-		shipyard::Workload::builder("updates")
-			.with_system(system!(systems::input))
-			.with_system(system!(systems::spawn))
-			.with_system(system!(systems::contactdamage))
-			.with_system(system!(systems::enemyai))
-			.with_system(system!(systems::selfdamage))
-			.with_system(system!(systems::death))
-			.with_system(system!(systems::camera))
-			.with_system(system!(systems::physics))
-			.add_to_world(&universe.world)
-			.unwrap();
+		workloads::register(&universe.world)?;
 
 		Ok(universe)
 	}
 
 	pub fn reset(&mut self) {
+		self.clear_world();
+		self.world.run(|mut rng: UniqueViewMut<Rng>| rng.reseed());
+		for state in &self.states {
+			self.init_state(state);
+		}
+	}
+
+	// scheduled tasks and contacts refer to entities, so they go together with them
+	fn clear_world(&self) {
 		self.world.run(|mut all_storages: AllStoragesViewMut| {
 			all_storages.clear();
 		});
-		self.state.borrow_mut().init(&self);
+		self.clear_references();
+	}
+
+	fn clear_references(&self) {
+		self.world.run(
+			|mut scheduler: UniqueViewMut<Scheduler>,
+			 mut contacts: UniqueViewMut<Contacts>,
+			 clock: UniqueView<Clock>| {
+				scheduler.clear();
+				scheduler.set_time(clock.time());
+				contacts.clear();
+			},
+		);
+		#[cfg(feature = "physics-rapier")]
+		self.world
+			.run(|mut rapier: UniqueViewMut<RapierWorld>| rapier.clear());
 	}
 
 	pub fn create_swapchain(&mut self, window: &Window, surface: &wgpu::Surface) {
-		if let Some(swap_chain_descriptor) = &create_swap_chain_descriptor(&window) {
+		if self.headless {
+			return;
+		}
+		if let Some(swap_chain_descriptor) =
+			&create_swap_chain_descriptor(&window, self.present_mode)
+		{
 			info!("recreating swapchain");
 			self.world.run(|mut renderer: UniqueViewMut<Renderer>| {
 				renderer.swapchain = Some(
@@ -73,72 +180,460 @@ impl Universe {
 				renderer.width = window.inner_size().width;
 				renderer.height = window.inner_size().height;
 			});
+			if !self.is_replaying() {
+				self.world.run(|mut input: UniqueViewMut<Input>| {
+					input.viewport = glam::Vec2::new(
+						window.inner_size().width as f32,
+						window.inner_size().height as f32,
+					)
+				});
+			}
+		}
+	}
+
+	// takes effect with the next swapchain
+	pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+		self.present_mode = present_mode;
+	}
+
+	pub fn set_debug_colliders(&mut self, enabled: bool) {
+		if !self.headless {
+			self.world
+				.run(|mut renderer: UniqueViewMut<Renderer>| renderer.debug_colliders = enabled);
 		}
 	}
 
 	pub fn push_state<T: State + Sized + 'static>(&mut self) {
-		let mut state = T::new(&self);
-		state.init(&self);
-		self.state = Box::from(RefCell::new(state));
+		self.apply(Transition::push::<T>());
+	}
+
+	pub fn pop_state(&mut self) { self.apply(Transition::Pop); }
+
+	pub fn replace_state<T: State + Sized + 'static>(&mut self) {
+		self.apply(Transition::replace::<T>());
+	}
+
+	pub fn switch_state<T: State + Sized + 'static>(&mut self) {
+		self.apply(Transition::switch::<T>());
+	}
+
+	// queues a transition to be applied after the current update, for use from within states
+	pub fn transition(&self, transition: Transition) {
+		self.transitions.borrow_mut().push(transition);
+	}
+
+	fn apply(&mut self, transition: Transition) {
+		match transition {
+			Transition::Push(builder) => {
+				if let Some(state) = self.states.last() {
+					state.borrow_mut().on_pause(&self);
+				}
+				self.enter(builder);
+			},
+			Transition::Pop => {
+				if let Some(state) = self.states.pop() {
+					state.borrow_mut().on_exit(&self);
+				}
+				if let Some(state) = self.states.last() {
+					state.borrow_mut().on_resume(&self);
+				}
+			},
+			Transition::Replace(builder) => {
+				if let Some(state) = self.states.pop() {
+					state.borrow_mut().on_exit(&self);
+				}
+				self.enter(builder);
+			},
+			Transition::Switch(builder) => {
+				while let Some(state) = self.states.pop() {
+					state.borrow_mut().on_exit(&self);
+				}
+				self.clear_world();
+				self.world
+					.run(|mut session: UniqueViewMut<Session>| session.clear());
+				self.enter(builder);
+			},
+		}
+		// keeps held keys, forgetting them would let their key repeat press them again
 		self.world
-			.run(|mut session: UniqueViewMut<Session>| session.clear());
+			.run(|mut input: UniqueViewMut<Input>| input.end_tick());
 		self.world
-			.run(|mut input: UniqueViewMut<Input>| input.clear());
+			.run(|mut actions: UniqueViewMut<Actions>| actions.clear_edges());
+	}
+
+	fn enter(&mut self, builder: StateBuilder) {
+		let state = builder(&self);
+		self.init_state(&state);
+		self.states.push(state);
+	}
+
+	fn init_state(&self, state: &RefCell<dyn State>) {
+		let scene = state.borrow().scene();
+		if let Some(scene) = scene {
+			if let Err(error) = self.load_scene(scene) {
+				error!("couldn't load scene {}: {}", scene, error);
+			}
+		}
+		state.borrow_mut().init(&self);
+	}
+
+	pub fn spawn_prefab(
+		&self, name: &str, overrides: &[SceneComponent],
+	) -> Result<EntityId, Error> {
+		self.world.run(|all_storages: AllStoragesViewMut| {
+			prefab::spawn_prefab(&all_storages, name, overrides)
+		})
+	}
+
+	pub fn load_scene(&self, name: &str) -> Result<SceneInstance, Error> {
+		let instance = Scene::load(name)?.instantiate(&self.world)?;
+		if let (Some(camera), false) = (instance.camera, self.headless) {
+			self.world
+				.run(|mut renderer: UniqueViewMut<Renderer>| renderer.camera = Some(camera));
+		}
+		Ok(instance)
+	}
+
+	fn apply_transitions(&mut self) {
+		let transitions = self.transitions.replace(Vec::new());
+		for transition in transitions {
+			self.apply(transition);
+		}
+	}
+
+	// index of the lowest state that still receives updates, states below are frozen
+	fn lowest_updated(&self) -> usize {
+		self.states
+			.iter()
+			.rposition(|state| state.borrow().is_blocking())
+			.unwrap_or(0)
+	}
+
+	// index of the lowest state that is still rendered, states below are hidden
+	fn lowest_rendered(&self) -> usize {
+		self.states
+			.iter()
+			.rposition(|state| !state.borrow().is_overlay())
+			.unwrap_or(0)
 	}
 
 	#[flame]
 	pub fn event(&mut self, event: Event<()>) {
+		let lowest = self.lowest_updated();
+		let consumed = self.states[lowest..]
+			.iter()
+			.rev()
+			.any(|state| state.borrow_mut().event(&self, &event));
+		self.apply_transitions();
+		if consumed || self.is_replaying() {
+			return;
+		}
 		self.world.run(|mut input: UniqueViewMut<Input>| {
 			match event {
 				Event::WindowEvent {
 					event:
-						winit::event::WindowEvent::KeyboardInput {
+						WindowEvent::KeyboardInput {
 							input:
 								winit::event::KeyboardInput {
-									virtual_keycode,
+									virtual_keycode: Some(key),
 									state,
 									..
 								},
 							..
 						},
 					..
+				} => match state {
+					ElementState::Pressed => input.press_key(key),
+					ElementState::Released => input.release_key(key),
+				},
+				Event::WindowEvent {
+					event: WindowEvent::MouseInput { button, state, .. },
+					..
+				} => match state {
+					ElementState::Pressed => input.press_button(button),
+					ElementState::Released => input.release_button(button),
+				},
+				Event::WindowEvent {
+					event: WindowEvent::CursorMoved { position, .. },
+					..
 				} => {
-					if let Some(key) = virtual_keycode {
-						if let ElementState::Pressed = state {
-							input.keys_down.insert(key);
-						}
-						if let ElementState::Released = state {
-							input.keys_down.remove(&key);
-						}
-					}
+					input.cursor = Some(glam::Vec2::new(position.x as f32, position.y as f32));
+				},
+				Event::WindowEvent {
+					event: WindowEvent::CursorLeft { .. },
+					..
+				} => {
+					input.cursor = None;
 				},
 				_ => (),
 			};
 		})
 	}
 
+	pub fn set_gamepad_source(&mut self, source: Box<dyn GamepadSource>) {
+		self.gamepads.set_source(source);
+	}
+
+	// gamepads are polled every frame but replays keep their recorded gamepad input
+	fn poll_gamepads(&mut self) {
+		self.gamepads.poll();
+		if !self.is_replaying() {
+			let gamepads = &mut self.gamepads;
+			self.world
+				.run(|mut input: UniqueViewMut<Input>| gamepads.apply(&mut input));
+		}
+	}
+
 	#[flame]
 	pub fn update(&mut self) {
-		let mut reset = false;
-		self.world.run(|input: UniqueView<Input>| {
-			if input.keys_down.contains(&VirtualKeyCode::R) {
-				reset = true;
+		self.world
+			.run(|mut clock: UniqueViewMut<Clock>| clock.update());
+		self.poll_gamepads();
+		self.simulate();
+	}
+
+	// advances by a fixed frame time in milliseconds instead of measuring the elapsed time
+	#[flame]
+	pub fn step(&mut self, frame_time: f32) {
+		self.world
+			.run(|mut clock: UniqueViewMut<Clock>| clock.advance(frame_time));
+		self.poll_gamepads();
+		self.simulate();
+	}
+
+	fn simulate(&mut self) {
+		self.world.run(systems::clear_events);
+		self.world
+			.run(|mut clock: UniqueViewMut<Clock>| clock.accumulate());
+		let mut ticks = 0;
+		while self
+			.world
+			.run(|mut clock: UniqueViewMut<Clock>| clock.consume_tick())
+		{
+			self.tick_input();
+			let lowest = self.lowest_updated();
+			for state in &self.states[lowest..] {
+				state.borrow_mut().tick(&self);
 			}
-		});
-		if reset {
-			self.reset();
+			self.apply_transitions();
+			if self
+				.world
+				.run(|actions: UniqueView<Actions>| actions.any_just_pressed(Action::Restart))
+			{
+				self.reset();
+			} else {
+				self.tick();
+			}
+			self.world
+				.run(|mut input: UniqueViewMut<Input>| input.end_tick());
+			ticks += 1;
+			if ticks >= MAX_TICKS_PER_FRAME {
+				self.world
+					.run(|mut clock: UniqueViewMut<Clock>| clock.discard());
+				break;
+			}
+		}
+
+		let lowest = self.lowest_updated();
+		for workload in self
+			.updated_workloads()
+			.iter()
+			.filter_map(|workloads| workloads.update)
+		{
+			self.world.run_workload(workload);
+		}
+		for state in &self.states[lowest..] {
+			state.borrow_mut().update(&self);
+		}
+
+		self.apply_transitions();
+	}
+
+	// runs the fixed workloads of the updated states, game time only advances while any run
+	fn tick(&mut self) {
+		let fixed = self
+			.updated_workloads()
+			.iter()
+			.filter_map(|workloads| workloads.fixed)
+			.collect::<Vec<_>>();
+		if !self
+			.world
+			.run(|mut clock: UniqueViewMut<Clock>| clock.step(!fixed.is_empty()))
+		{
+			return;
+		}
+		for workload in fixed {
+			self.world.run_workload(workload);
+		}
+	}
+
+	fn updated_workloads(&self) -> Vec<Workloads> {
+		self.states[self.lowest_updated()..]
+			.iter()
+			.map(|state| state.borrow().workloads())
+			.collect()
+	}
+
+	// asks the states to pause with the next tick, recorded like any other input
+	pub fn request_pause(&mut self) {
+		if self.is_replaying() {
 			return;
 		}
 		self.world
-			.run(|mut timer: UniqueViewMut<Timer>| timer.update());
+			.run(|mut input: UniqueViewMut<Input>| input.pause_requested = true);
+	}
+
+	// replaces or records the input for the upcoming tick
+	fn tick_input(&mut self) {
+		let tick = self.world.run(|clock: UniqueView<Clock>| clock.ticks());
+		if self.is_replay_finished() {
+			// the player takes over after the last recorded tick, without the keys held in it
+			info!("replay finished");
+			self.replay = None;
+			let viewport = if self.headless {
+				None
+			} else {
+				Some(self.world.run(|renderer: UniqueView<Renderer>| {
+					glam::Vec2::new(renderer.width as f32, renderer.height as f32)
+				}))
+			};
+			self.world.run(|mut input: UniqueViewMut<Input>| {
+				input.clear();
+				if let Some(viewport) = viewport {
+					input.viewport = viewport;
+				}
+			});
+		}
+		if let Some(replay) = self.replay.as_mut() {
+			let input = replay.input(tick).clone();
+			self.world
+				.run(|mut current: UniqueViewMut<Input>| *current = input);
+		}
+		let world = &self.world;
+		let failed = self.recorder.as_mut().and_then(|recorder| {
+			world
+				.run(|input: UniqueView<Input>| recorder.record(tick, &input))
+				.err()
+		});
+		if let Some(error) = failed {
+			warn!("stopped recording: {}", error);
+			self.recorder = None;
+		}
+		self.world.run(
+			|input: UniqueView<Input>,
+			 bindings: UniqueView<Bindings>,
+			 mut actions: UniqueViewMut<Actions>| actions.update(&bindings, &input),
+		);
+	}
+
+	pub fn start_recording(&mut self, path: &Path) -> Result<(), Error> {
+		let seed = self.seed();
+		let bindings = self
+			.world
+			.run(|bindings: UniqueView<Bindings>| bindings.clone());
+		self.recorder = Some(Recorder::create(path, seed, TICK_RATE, &bindings)?);
+		info!("recording to {}", path.display());
+		Ok(())
+	}
+
+	pub fn stop_recording(&mut self) {
+		if let Some(recorder) = self.recorder.take() {
+			if let Err(error) = recorder.finish() {
+				warn!("couldn't finish recording: {}", error);
+			}
+		}
+	}
+
+	pub fn start_replay(&mut self, path: &Path) -> Result<(), Error> {
+		let replay = Replay::open(path, TICK_RATE)?;
+		self.world
+			.run(|mut rng: UniqueViewMut<Rng>| *rng = Rng::new(replay.seed()));
+		// the recorded input only means the same thing under the bindings it was recorded with
+		self.world
+			.run(|mut bindings: UniqueViewMut<Bindings>| *bindings = replay.bindings().clone());
+		self.replay = Some(replay);
+		info!("replaying {}", path.display());
+		Ok(())
+	}
+
+	pub fn is_replaying(&self) -> bool {
+		self.replay
+			.as_ref()
+			.map_or(false, |replay| !replay.is_finished())
+	}
+
+	pub fn is_replay_finished(&self) -> bool {
+		self.replay.as_ref().map_or(false, Replay::is_finished)
+	}
+
+	pub fn save(&self, path: &Path) -> Result<(), Error> {
+		let camera = if self.headless {
+			None
+		} else {
+			self.world
+				.run(|renderer: UniqueView<Renderer>| renderer.camera)
+		};
+		save::save(&self.world, path, camera)
+	}
+
+	pub fn load(&self, path: &Path) -> Result<(), Error> {
+		let camera = save::load(&self.world, path)?;
+		self.clear_references();
+		if !self.headless {
+			self.world
+				.run(|mut renderer: UniqueViewMut<Renderer>| renderer.camera = camera);
+		}
+		Ok(())
+	}
 
-		self.world.run_workload("updates");
+	// replays can't depend on the quicksave of the machine they run on, nor overwrite it
+	fn quicksave_blocked(&self) -> bool {
+		let blocked = self.recorder.is_some() || self.replay.is_some();
+		if blocked {
+			info!("quicksaves are disabled while recording or replaying");
+		}
+		blocked
+	}
 
-		self.state.borrow_mut().update(&self);
+	pub fn quicksave(&self) {
+		if self.quicksave_blocked() {
+			return;
+		}
+		if let Some(path) = save::quicksave_path() {
+			match self.save(&path) {
+				Ok(()) => info!("saved to {}", path.display()),
+				Err(error) => warn!("couldn't save to {}: {}", path.display(), error),
+			}
+		}
 	}
 
+	pub fn quickload(&self) {
+		if self.quicksave_blocked() {
+			return;
+		}
+		if let Some(path) = save::quicksave_path() {
+			match self.load(&path) {
+				Ok(()) => info!("loaded from {}", path.display()),
+				Err(error) => warn!("couldn't load from {}: {}", path.display(), error),
+			}
+		}
+	}
+
+	pub fn seed(&self) -> u64 { self.world.run(|rng: UniqueView<Rng>| rng.seed()) }
+
 	#[flame]
-	pub fn render(&mut self) { self.world.run(systems::render); }
+	pub fn render(&mut self) {
+		if self.headless {
+			return;
+		}
+		let lowest = self.lowest_rendered();
+		for state in &self.states[lowest..] {
+			if let Some(workload) = state.borrow().workloads().render {
+				self.world.run_workload(workload);
+			}
+			state.borrow_mut().render(&self);
+		}
+	}
 
 	pub fn get_status(&self) -> String { self.world.run(systems::status) }
 }
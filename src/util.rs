@@ -1,4 +1,11 @@
-use crate::resources;
+use crate::{config::PresentMode, resources};
+use std::path::PathBuf;
+
+
+pub struct CrashInfo {
+	pub seed: u64,
+	pub recording: Option<PathBuf>,
+}
 
 pub fn create_version_string() -> String {
 	use std::env::consts::{ARCH, OS};
@@ -17,7 +24,7 @@ pub fn create_version_string() -> String {
 }
 
 pub fn create_panic_hook(
-	adapter_info: Option<wgpu::AdapterInfo>,
+	adapter_info: Option<wgpu::AdapterInfo>, crash_info: Option<CrashInfo>,
 ) -> Box<dyn Fn(&std::panic::PanicInfo<'_>) + 'static + Sync + Send> {
 	std::boxed::Box::new(move |panic| {
 		use color_backtrace::{
@@ -35,6 +42,14 @@ pub fn create_panic_hook(
 			let _ = writeln!(file, "Version information:");
 			let _ = writeln!(file, "\t{}", create_version_string());
 			let _ = writeln!(file);
+			if let Some(crash_info) = &crash_info {
+				let _ = writeln!(file, "Session information:");
+				let _ = writeln!(file, "\tSeed: {}", crash_info.seed);
+				if let Some(recording) = &crash_info.recording {
+					let _ = writeln!(file, "\tRecording: {}", recording.display());
+				}
+				let _ = writeln!(file);
+			}
 			let _ = writeln!(file, "System information:");
 			let os = os_info::get();
 			let _ = writeln!(
@@ -90,8 +105,18 @@ pub fn create_window(
 	builder.build(&eventloop).unwrap()
 }
 
+// fifo is the only mode every surface supports, wgpu checks the surface capabilities when creating
+// the swapchain and falls back to it with a warning when the requested mode isn't supported
+pub fn get_present_mode(present_mode: PresentMode) -> wgpu::PresentMode {
+	match present_mode {
+		PresentMode::Vsync => wgpu::PresentMode::Fifo,
+		PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+		PresentMode::Immediate => wgpu::PresentMode::Immediate,
+	}
+}
+
 pub fn create_swap_chain_descriptor(
-	window: &winit::window::Window,
+	window: &winit::window::Window, present_mode: wgpu::PresentMode,
 ) -> Option<wgpu::SwapChainDescriptor> {
 	let window_size = window.inner_size();
 	if window_size.width == 0 || window_size.height == 0 {
@@ -102,6 +127,6 @@ pub fn create_swap_chain_descriptor(
 		format: wgpu::TextureFormat::Bgra8Unorm,
 		width: window_size.width,
 		height: window_size.height,
-		present_mode: wgpu::PresentMode::Mailbox,
+		present_mode,
 	})
 }
@@ -0,0 +1,65 @@
+use anyhow::Error;
+use shipyard::{system, Workload, World};
+
+#[cfg(feature = "physics-rapier")]
+use crate::rapier;
+use crate::systems;
+
+
+pub const SHOOTER_FIXED: &str = "shooter_fixed";
+pub const SHOOTER_UPDATE: &str = "shooter_update";
+pub const SHOOTER_RENDER: &str = "shooter_render";
+
+
+#[derive(Clone, Copy, Default)]
+pub struct Workloads {
+	// run once per fixed tick
+	pub fixed: Option<&'static str>,
+	// run once per frame
+	pub update: Option<&'static str>,
+	// run once per frame while the state is visible and a renderer is present
+	pub render: Option<&'static str>,
+}
+
+// systems run in the order they are listed, systems without conflicting borrows may run in parallel
+pub fn register(world: &World) -> Result<(), Error> {
+	let mut fixed = Workload::builder(SHOOTER_FIXED);
+	fixed
+		.with_system(system!(systems::events))
+		.with_system(system!(systems::schedule))
+		.with_system(system!(systems::snapshot))
+		.with_system(system!(systems::aim))
+		.with_system(system!(systems::input))
+		.with_system(system!(systems::spawn))
+		.with_system(system!(systems::broadphase))
+		.with_system(system!(systems::collision));
+	// rapier resolves solid contacts itself
+	#[cfg(not(feature = "physics-rapier"))]
+	fixed.with_system(system!(systems::collisionresponse));
+	fixed
+		.with_system(system!(systems::knockback))
+		.with_system(system!(systems::contactdamage))
+		.with_system(system!(systems::enemyai))
+		.with_system(system!(systems::selfdamage))
+		.with_system(system!(systems::death))
+		.with_system(system!(systems::score))
+		.with_system(system!(systems::hitstop));
+	#[cfg(not(feature = "physics-rapier"))]
+	fixed.with_system(system!(systems::physics));
+	#[cfg(feature = "physics-rapier")]
+	fixed
+		.with_system(system!(rapier::sync))
+		.with_system(system!(rapier::step))
+		.with_system(system!(rapier::writeback));
+	fixed.add_to_world(world)?;
+
+	Workload::builder(SHOOTER_UPDATE)
+		.with_system(system!(systems::camera))
+		.add_to_world(world)?;
+
+	Workload::builder(SHOOTER_RENDER)
+		.with_system(system!(systems::render))
+		.add_to_world(world)?;
+
+	Ok(())
+}